@@ -0,0 +1,320 @@
+use crate::{
+    level,
+    map::{CellError, Coordinates, Direction, Format, Map, MapError, Tile},
+    sim::GameState,
+    solver::{self, Unsolved},
+    tiled,
+};
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// A problem found in a level file, located by its 1-based line and column when possible.
+#[derive(Debug)]
+pub struct Problem {
+    path: PathBuf,
+    location: Option<(usize, usize)>,
+    message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some((line, column)) = self.location {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The result of checking a level.
+#[derive(Debug)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    /// Length of the shortest solution, if the level could be solved.
+    pub solution: Option<usize>,
+    pub par: Option<usize>,
+}
+
+/// Check the level at `path` for structural problems and make sure it can be won.
+pub fn check<P: AsRef<Path>>(path: P) -> Result<Report, MapError> {
+    let path = path.as_ref();
+    let metadata = level::metadata(path)?;
+    let mut problems = Vec::new();
+    let problem = |location, message| Problem {
+        path: path.to_path_buf(),
+        location,
+        message,
+    };
+    let mut starts = Vec::new();
+    let mut goals = Vec::new();
+    let mut teleporters = BTreeMap::new();
+    if tiled::is_tiled(path) {
+        // Tiled maps are checked by their importer, which cannot tell where a tile was defined.
+        for (_, tile) in Map::load(path)?.tiles() {
+            match tile {
+                Tile::Start => starts.push(None),
+                Tile::Goal => goals.push(None),
+                tile if tile.is_teleporter() => teleporters
+                    .entry(tile as u8)
+                    .or_insert_with(|| ((tile as u8).to_string(), Vec::new()))
+                    .1
+                    .push(None),
+                _ => {}
+            }
+        }
+    } else {
+        let rows: Vec<String> = File::open(path)
+            .and_then(|file| BufReader::new(file).lines().collect())
+            .map_err(|source| MapError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        if rows.is_empty() {
+            problems.push(problem(None, "level is empty".to_string()));
+        }
+        let format = Format::from_path(path).unwrap_or(Format::Csv);
+        let width = rows.first().map_or(0, |row| format.cells(row).len());
+        for (i, row) in rows.iter().enumerate() {
+            let line = i + 1;
+            let cells = format.cells(row);
+            for &(column, value) in cells.iter() {
+                let location = Some((line, column));
+                match format.parse(value) {
+                    Ok(Tile::Start) => starts.push(location),
+                    Ok(Tile::Goal) => goals.push(location),
+                    Ok(tile) if tile.is_teleporter() => teleporters
+                        .entry(tile as u8)
+                        .or_insert_with(|| (value.to_string(), Vec::new()))
+                        .1
+                        .push(location),
+                    Ok(_) => {}
+                    Err(CellError::UnknownTile) => {
+                        problems.push(problem(location, format!("unknown tile `{}`", value)))
+                    }
+                    Err(CellError::NotANumber) => problems.push(problem(
+                        location,
+                        format!("`{}` is not a tile value", value),
+                    )),
+                }
+            }
+            let columns = cells.len();
+            if columns != width {
+                problems.push(problem(
+                    Some((line, 1)),
+                    format!(
+                        "row has {} columns but the first row has {}",
+                        columns, width
+                    ),
+                ));
+            }
+        }
+    }
+    match starts.as_slice() {
+        [] => problems.push(problem(None, "level has no start tile".to_string())),
+        [_] => {}
+        [first, duplicates @ ..] => {
+            let message = match first {
+                Some((line, column)) => {
+                    format!("duplicate start tile, first defined at {}:{}", line, column)
+                }
+                None => "duplicate start tile".to_string(),
+            };
+            for location in duplicates {
+                problems.push(problem(*location, message.clone()));
+            }
+        }
+    }
+    // A level may have any number of goals, but it needs at least one.
+    if goals.is_empty() {
+        problems.push(problem(None, "level has no goal tile".to_string()));
+    }
+    for (value, locations) in teleporters.into_values() {
+        let message = match locations.len() {
+            1 => format!("teleporter `{}` has no partner", value),
+            2 => continue,
+            count => format!(
+                "teleporter `{}` appears {} times instead of twice",
+                value, count
+            ),
+        };
+        problems.push(problem(locations[0], message));
+    }
+    let par = metadata.par;
+    if !problems.is_empty() {
+        return Ok(Report {
+            problems,
+            solution: None,
+            par,
+        });
+    }
+
+    let game_state = GameState::new(Map::load(path)?, metadata.rules);
+    let solution = match shortest_solution(&game_state, &AtomicBool::new(false)) {
+        Ok(moves) => {
+            if let Some(par) = par.filter(|&par| par < moves) {
+                problems.push(problem(
+                    None,
+                    format!(
+                        "par of {} is below the shortest solution of {} moves",
+                        par, moves
+                    ),
+                ));
+            }
+            Some(moves)
+        }
+        Err(message) => {
+            problems.push(problem(None, message.to_string()));
+            None
+        }
+    };
+    Ok(Report {
+        problems,
+        solution,
+        par,
+    })
+}
+
+/// Number of moves of the shortest solution of a level, or why it cannot be won.
+///
+/// Levels whose goal or gems cannot be reached at all are rejected before searching for a
+/// solution.
+pub fn shortest_solution(
+    game_state: &GameState,
+    cancel: &AtomicBool,
+) -> Result<usize, &'static str> {
+    let map = game_state.map();
+    let reachable = reachable(map, game_state.player());
+    let goal_reachable = reachable
+        .iter()
+        .any(|coordinates| matches!(map[coordinates.y()][coordinates.x()], Tile::Goal));
+    if !goal_reachable {
+        return Err("no goal is reachable from the start tile");
+    }
+    // The goal only opens once every gem has been collected.
+    let gems_reachable = map
+        .tiles()
+        .filter(|&(_, tile)| tile == Tile::Gem)
+        .all(|(coordinates, _)| reachable.contains(&coordinates));
+    if !gems_reachable {
+        return Err("not every gem is reachable from the start tile");
+    }
+    match solver::solve(game_state, solver::MAX_STATES, cancel) {
+        Ok(moves) => Ok(moves.len()),
+        Err(Unsolved::Unwinnable) => Err("no sequence of moves wins the level"),
+        Err(Unsolved::GaveUp) => Err("search gave up before finding a solution"),
+        Err(Unsolved::Cancelled) => Err("search was cancelled"),
+    }
+}
+
+/// Shown in place of the summary of a level while its solution is being searched for.
+pub const SEARCHING: &str = "Searching for the shortest solution...";
+
+/// The length of the shortest solution of a level, or a warning if it cannot be won.
+pub fn summary(game_state: &GameState, cancel: &AtomicBool) -> String {
+    match shortest_solution(game_state, cancel) {
+        Ok(moves) => format!("Shortest solution: {} moves", moves),
+        Err(message) => format!("Warning: {}", message),
+    }
+}
+
+/// A search for the shortest solution of a level, which runs on a background thread so that large
+/// levels do not freeze the game.
+#[derive(Debug)]
+pub struct PendingCheck {
+    task: Task<String>,
+    /// Set once the check is dropped, so that the search stops early.
+    cancel: Arc<AtomicBool>,
+}
+
+impl PendingCheck {
+    pub fn spawn(pool: &AsyncComputeTaskPool, game_state: GameState) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let task = pool.spawn(async move { summary(&game_state, &flag) });
+        Self { task, cancel }
+    }
+
+    /// The summary of the level, once the search has finished.
+    pub fn poll(&mut self) -> Option<String> {
+        future::block_on(future::poll_once(&mut self.task))
+    }
+}
+
+impl Drop for PendingCheck {
+    fn drop(&mut self) {
+        // Dropping the task only cancels a search that has not started yet, since the search never
+        // yields to the executor.
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Drop the search for the level being left, which stops it.
+pub fn stop_check(mut commands: Commands) {
+    commands.remove_resource::<PendingCheck>();
+}
+
+/// Every tile the player could safely walk to from `start` if there were no enemies and every
+/// door and gate were open.
+fn reachable(map: &Map, start: Coordinates) -> HashSet<Coordinates> {
+    let mut map = map.clone();
+    map.replace(|tile| match tile {
+        Tile::Block => Tile::Block,
+        tile if tile.is_hazard() => Tile::Block,
+        tile if tile.is_solid() => Tile::Empty,
+        tile => tile,
+    });
+    let mut visited = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(coordinates) = stack.pop() {
+        for direction in Direction::ALL {
+            let mut next = coordinates;
+            if next.move_in(direction, &map) {
+                // A crate on the partner keeps the player on the teleporter, so both can be reached.
+                for next in [Some(next), map.partner(next)].into_iter().flatten() {
+                    if visited.insert(next) {
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+    }
+    visited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::Rules;
+
+    #[test]
+    fn walled_off_gem_is_reported() {
+        let game_state = GameState::new(Map::from_ascii(&["S.G", "###", "..*"]), Rules::default());
+        assert_eq!(
+            shortest_solution(&game_state, &AtomicBool::new(false)),
+            Err("not every gem is reachable from the start tile")
+        );
+    }
+
+    #[test]
+    fn teleporter_tile_is_reachable_as_well_as_its_partner() {
+        // With a crate on the partner, the player stays on the teleporter and can walk on.
+        let map = Map::from_ascii(&["S1.", "###", ".1."]);
+        let reachable = reachable(&map, Coordinates::new(1, 1));
+        assert!(reachable.contains(&Coordinates::new(3, 1)));
+        assert!(reachable.contains(&Coordinates::new(1, 3)));
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{map::Coordinates, sim::GameState};
+use bevy::prelude::*;
+
+/// A pushable crate, identified by its index in the simulation's crate list.
+#[derive(Component, Debug)]
+pub struct Crate(usize);
+
+impl Crate {
+    pub const COLOR: Color = Color::rgb(0.66, 0.5, 0.36);
+
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+pub fn crate_sync(
+    game_state: Res<GameState>,
+    mut crate_query: Query<(&mut Transform, &mut Coordinates, &Crate)>,
+) {
+    if game_state.is_changed() {
+        for (mut transform, mut coordinates, pushable) in crate_query.iter_mut() {
+            *coordinates = game_state.crates()[pushable.index()];
+            transform.translation = game_state.map().translation(*coordinates);
+        }
+    }
+}
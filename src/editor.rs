@@ -0,0 +1,312 @@
+use crate::{
+    check::{self, PendingCheck},
+    crates::Crate,
+    level::{self, LevelManager},
+    map::{Coordinates, Map, Tile},
+    sim::{GameState, Rules},
+    AppState, TILE_SIZE,
+};
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+use std::{
+    convert::TryFrom,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Color of empty tiles, lighter than the background so that the grid can be seen.
+const EMPTY_COLOR: Color = Color::rgb(0.22, 0.24, 0.3);
+/// Largest number of rows or columns a level can be resized to.
+const MAX_SIZE: usize = 32;
+
+/// Level being built in the editor, which is kept while it is test played.
+#[derive(Debug)]
+pub struct Editor {
+    rows: Vec<Vec<Tile>>,
+    /// Tile painted with the left mouse button.
+    brush: Tile,
+    /// Name the level was last saved under.
+    name: Option<String>,
+    /// Outcome of the last save, or why the level could not be test played.
+    status: String,
+    /// Shortest solution of the level as it was last saved, or a note that it is being searched.
+    solution: Option<String>,
+    /// Whether the mouse buttons were let go since entering the editor, so that the click on the
+    /// menu button does not paint a tile.
+    mouse_released: bool,
+}
+
+#[derive(Component, Debug)]
+pub struct EditorTile;
+
+impl Default for Editor {
+    fn default() -> Self {
+        let mut rows = vec![vec![Tile::Empty; 9]; 9];
+        rows[8][0] = Tile::Start;
+        rows[0][8] = Tile::Goal;
+        Self {
+            rows,
+            brush: Tile::Block,
+            name: None,
+            status: String::new(),
+            solution: None,
+            mouse_released: false,
+        }
+    }
+}
+
+impl Editor {
+    pub fn map(&self) -> Map {
+        Map::from_rows(&self.rows)
+    }
+
+    pub fn brush(&self) -> Tile {
+        self.brush
+    }
+
+    /// Number of columns and rows of the level, not counting its walls.
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows[0].len(), self.rows.len())
+    }
+
+    /// Outcome of the last save, followed by whether the level can be won.
+    pub fn status(&self) -> String {
+        match &self.solution {
+            Some(solution) => format!("{}\n{}", self.status, solution),
+            None => self.status.clone(),
+        }
+    }
+
+    /// Name the level is saved and test played under.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("untitled")
+    }
+
+    /// Tile at the given coordinates of the walled map.
+    fn tile(&self, coordinates: Coordinates) -> Tile {
+        self.rows[coordinates.y() - 1][coordinates.x() - 1]
+    }
+
+    /// Place `tile` at the given coordinates of the walled map.
+    fn paint(&mut self, coordinates: Coordinates, tile: Tile) {
+        // A level has a single start, so placing one moves it.
+        if tile == Tile::Start {
+            for row in self.rows.iter_mut() {
+                for cell in row.iter_mut().filter(|cell| **cell == Tile::Start) {
+                    *cell = Tile::Empty;
+                }
+            }
+        }
+        self.rows[coordinates.y() - 1][coordinates.x() - 1] = tile;
+    }
+
+    /// Add or remove columns on the right and rows at the bottom.
+    fn resize(&mut self, columns: isize, rows: isize) {
+        let (width, height) = self.size();
+        let clamp =
+            |size: usize, change: isize| (size as isize + change).clamp(1, MAX_SIZE as isize);
+        let (width, height) = (clamp(width, columns) as usize, clamp(height, rows) as usize);
+        self.rows.resize(height, vec![Tile::Empty; width]);
+        for row in self.rows.iter_mut() {
+            row.resize(width, Tile::Empty);
+        }
+    }
+
+    /// Select the tile `offset` values away from the brush, wrapping around.
+    fn cycle_brush(&mut self, offset: isize) {
+        let value = (self.brush as isize + offset).rem_euclid(Tile::COUNT as isize);
+        self.brush = Tile::try_from(value as u8).unwrap_or(Tile::Empty);
+    }
+
+    /// Write the level as a CSV file into the level directory.
+    ///
+    /// A level that was not saved before is named after the first number no level uses yet.
+    fn save(&mut self, level_manager: &LevelManager) -> io::Result<PathBuf> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => (1..)
+                .map(|number: usize| number.to_string())
+                .find(|name| level_manager.find(name).is_none())
+                .unwrap_or_default(),
+        };
+        let contents: String = self
+            .rows
+            .iter()
+            .map(|row| {
+                let values: Vec<String> =
+                    row.iter().map(|tile| (*tile as u8).to_string()).collect();
+                values.join(",") + "\n"
+            })
+            .collect();
+        let directory = level::directory();
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("{}.csv", name));
+        fs::write(&path, contents)?;
+        self.name = Some(name);
+        Ok(path)
+    }
+}
+
+/// Color a tile is drawn with in the editor, showing enemies and crates on the tiles they start on.
+fn color(tile: Tile) -> Color {
+    match tile {
+        Tile::Empty => EMPTY_COLOR,
+        Tile::Crate => Crate::COLOR,
+        tile => match tile.enemy_kind() {
+            Some(kind) => kind.color(false),
+            None => tile.color(),
+        },
+    }
+}
+
+/// Coordinates of the walled map under the cursor, if it is over a tile that can be painted.
+fn cursor_coordinates(window: &Window, map: &Map) -> Option<Coordinates> {
+    // The camera is centered on the map, which is centered on the origin.
+    let position = window.cursor_position()? - Vec2::new(window.width(), window.height()) / 2.0;
+    let x = (position.x / TILE_SIZE).round() as isize + (map.width / 2) as isize;
+    let y = (map.height / 2) as isize - (position.y / TILE_SIZE).round() as isize;
+    if x < 1 || y < 1 || x as usize + 1 >= map.width || y as usize + 1 >= map.height {
+        None
+    } else {
+        Some(Coordinates::new(x as usize, y as usize))
+    }
+}
+
+pub fn editor_setup(mut commands: Commands, mut editor: ResMut<Editor>) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    // Forget the last save, which also redraws the level.
+    editor.status.clear();
+    editor.solution = None;
+    editor.mouse_released = false;
+}
+
+pub fn editor_input(
+    mut editor: ResMut<Editor>,
+    mut level_manager: ResMut<LevelManager>,
+    mut state: ResMut<State<AppState>>,
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    let pressed = mouse_input.pressed(MouseButton::Left) || mouse_input.pressed(MouseButton::Right);
+    if !editor.mouse_released && !pressed {
+        editor.mouse_released = true;
+    }
+    if let Some(window) = windows.get_primary().filter(|_| editor.mouse_released) {
+        let tile = if mouse_input.pressed(MouseButton::Left) {
+            Some(editor.brush)
+        } else if mouse_input.pressed(MouseButton::Right) {
+            Some(Tile::Empty)
+        } else {
+            None
+        };
+        if let (Some(tile), Some(coordinates)) = (tile, cursor_coordinates(window, &editor.map())) {
+            // Only touch the level when it changes, so it is not redrawn every frame.
+            if editor.tile(coordinates) != tile {
+                editor.paint(coordinates, tile);
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::LBracket) {
+        editor.cycle_brush(-1);
+    }
+    if keyboard_input.just_pressed(KeyCode::RBracket) {
+        editor.cycle_brush(1);
+    }
+    for (code, columns, rows) in [
+        (KeyCode::Left, -1, 0),
+        (KeyCode::Right, 1, 0),
+        (KeyCode::Up, 0, -1),
+        (KeyCode::Down, 0, 1),
+    ] {
+        if keyboard_input.just_pressed(code) {
+            editor.resize(columns, rows);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        // Stay in the editor rather than test play a level that cannot be played.
+        let map = editor.map();
+        match map.validate(Path::new(editor.name())) {
+            Ok(()) => {
+                level_manager.start_test(editor.name().to_string(), map);
+                state.set(AppState::InGame).unwrap();
+            }
+            Err(err) => editor.status = format!("Cannot test play {}", err),
+        }
+    }
+}
+
+/// Save the level on Ctrl+S and start searching for its shortest solution.
+pub fn editor_save(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut level_manager: ResMut<LevelManager>,
+    pool: Res<AsyncComputeTaskPool>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    let control =
+        keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl);
+    if control && keyboard_input.just_pressed(KeyCode::S) {
+        editor.status = match editor.save(&level_manager) {
+            Ok(path) => {
+                // Pick up the new level in the level select menu.
+                match LevelManager::fetch() {
+                    Ok(levels) => *level_manager = levels,
+                    Err(err) => warn!("Failed to reload levels: {}", err),
+                }
+                // Replacing an unfinished search stops it.
+                let game_state = GameState::new(editor.map(), Rules::default());
+                commands.insert_resource(PendingCheck::spawn(&pool, game_state));
+                editor.solution = Some(check::SEARCHING.to_string());
+                format!("Saved to {}", path.display())
+            }
+            Err(err) => format!("Failed to save: {}", err),
+        };
+    }
+}
+
+/// Show the shortest solution of the saved level once the search for it has finished.
+pub fn editor_check(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    check: Option<ResMut<PendingCheck>>,
+) {
+    if let Some(solution) = check.and_then(|mut check| check.poll()) {
+        commands.remove_resource::<PendingCheck>();
+        editor.solution = Some(solution);
+    }
+}
+
+/// Redraw the level whenever it changes.
+pub fn editor_render(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    tile_query: Query<Entity, With<EditorTile>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let map = editor.map();
+    for (coordinates, tile) in map.tiles() {
+        let mut entity = commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                // Leave a gap between tiles to show the grid.
+                custom_size: Some(Vec2::splat(TILE_SIZE - 2.0)),
+                color: color(tile),
+                ..Sprite::default()
+            },
+            transform: Transform::from_translation(map.translation(coordinates)),
+            ..SpriteBundle::default()
+        });
+        entity.insert(EditorTile);
+        if let Some(arrow) = tile.arrow() {
+            entity.with_children(|parent| {
+                parent.spawn_bundle(crate::arrow_marker(arrow));
+            });
+        }
+    }
+}
@@ -1,51 +1,74 @@
 use crate::{
-    map::{Coordinates, Map},
-    player::{Player, PlayerMovementEvent},
+    map::{Coordinates, Direction},
+    sim::GameState,
 };
 use bevy::prelude::*;
-use std::cmp::Ordering;
 
 #[derive(Component, Debug)]
-pub struct Enemy;
+pub struct Enemy(usize);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnemyKind {
+    /// Steps towards the player, preferring the horizontal axis.
+    Chaser,
+    /// Steps towards the player, preferring the vertical axis.
+    VerticalChaser,
+    /// Moves in the opposite direction to the player.
+    Mirror,
+    /// Walks back and forth, starting in the given direction and turning around when blocked.
+    Patroller(Direction),
+    /// Stays put until the player is in its line of sight, then chases like a chaser.
+    Sleeper,
+    /// Chases like a chaser, but takes two steps per turn.
+    Fast,
+    /// Follows a shortest path around blocks towards the player.
+    Hunter,
+}
+
+impl EnemyKind {
+    pub fn color(&self, awake: bool) -> Color {
+        match self {
+            EnemyKind::Chaser => Color::rgb(0.95, 0.38, 0.42),
+            EnemyKind::VerticalChaser => Color::rgb(0.92, 0.52, 0.36),
+            EnemyKind::Mirror => Color::rgb(0.67, 0.49, 0.85),
+            EnemyKind::Patroller(_) => Color::rgb(0.85, 0.62, 0.25),
+            EnemyKind::Sleeper if awake => Color::rgb(0.95, 0.38, 0.42),
+            EnemyKind::Sleeper => Color::rgb(0.55, 0.36, 0.4),
+            EnemyKind::Fast => Color::rgb(0.98, 0.24, 0.62),
+            EnemyKind::Hunter => Color::rgb(0.78, 0.2, 0.24),
+        }
+    }
+}
 
 impl Enemy {
-    pub const COLOR: Color = Color::rgb(0.95, 0.38, 0.42);
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    /// Index of this enemy in the simulation's enemy list.
+    pub fn index(&self) -> usize {
+        self.0
+    }
 }
 
-pub fn enemy_movement(
-    map: Res<Map>,
-    mut enemy_query: Query<(&mut Transform, &mut Coordinates), With<Enemy>>,
-    player_query: Query<&Coordinates, (With<Player>, Without<Enemy>)>,
-    mut events: EventReader<PlayerMovementEvent>,
-) {
-    let player_coordinates = player_query.single();
-    for _ in events.iter() {
-        for (mut enemy_transform, mut enemy_coordinate) in enemy_query.iter_mut() {
-            let (delta_x, delta_y) = (
-                enemy_coordinate.x() as isize - player_coordinates.x() as isize,
-                enemy_coordinate.y() as isize - player_coordinates.y() as isize,
-            );
-            if delta_x.abs() >= delta_y.abs() {
-                match delta_x.cmp(&0) {
-                    Ordering::Greater => {
-                        enemy_coordinate.move_left(&mut enemy_transform.translation, &map);
-                    }
-                    Ordering::Less => {
-                        enemy_coordinate.move_right(&mut enemy_transform.translation, &map);
-                    }
-                    _ => {}
-                }
-            } else {
-                match delta_y.cmp(&0) {
-                    Ordering::Greater => {
-                        enemy_coordinate.move_up(&mut enemy_transform.translation, &map)
-                    }
-                    Ordering::Less => {
-                        enemy_coordinate.move_down(&mut enemy_transform.translation, &map)
-                    }
-                    _ => {}
-                }
-            }
+type EnemySprite<'a> = (
+    &'a mut Transform,
+    &'a mut Coordinates,
+    &'a mut Sprite,
+    &'a mut Visibility,
+    &'a Enemy,
+);
+
+pub fn enemy_sync(game_state: Res<GameState>, mut enemy_query: Query<EnemySprite>) {
+    if game_state.is_changed() {
+        for (mut transform, mut coordinates, mut sprite, mut visibility, enemy) in
+            enemy_query.iter_mut()
+        {
+            let state = game_state.enemies()[enemy.index()];
+            *coordinates = state.coordinates;
+            transform.translation = game_state.map().translation(*coordinates);
+            sprite.color = state.kind.color(state.awake);
+            visibility.is_visible = state.alive;
         }
     }
 }
@@ -0,0 +1,58 @@
+use crate::{
+    map::Direction,
+    sim::{GameState, Snapshot, StepOutcome},
+};
+
+/// Moves made during the current attempt that can be undone and redone.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<(Snapshot, Direction)>,
+    redo: Vec<Direction>,
+}
+
+impl History {
+    /// Play a new move, forgetting any moves that could have been redone.
+    pub fn step(&mut self, game_state: &mut GameState, direction: Direction) -> StepOutcome {
+        self.redo.clear();
+        self.play(game_state, direction)
+    }
+
+    /// Number of moves made during the current attempt, not counting undone moves.
+    pub fn moves(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Restore the state from before the last move, returning whether there was one.
+    pub fn undo(&mut self, game_state: &mut GameState) -> bool {
+        match self.undo.pop() {
+            Some((snapshot, direction)) => {
+                game_state.restore(snapshot);
+                self.redo.push(direction);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replay the last undone move.
+    pub fn redo(&mut self, game_state: &mut GameState) -> Option<StepOutcome> {
+        let direction = self.redo.pop()?;
+        Some(self.play(game_state, direction))
+    }
+
+    /// Start the attempt over, forgetting every move made during it.
+    pub fn reset(&mut self, game_state: &mut GameState) {
+        game_state.reset();
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn play(&mut self, game_state: &mut GameState, direction: Direction) -> StepOutcome {
+        let snapshot = game_state.snapshot().clone();
+        let outcome = game_state.step(direction);
+        if outcome != StepOutcome::Blocked {
+            self.undo.push((snapshot, direction));
+        }
+        outcome
+    }
+}
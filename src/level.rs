@@ -1,33 +1,108 @@
-use crate::map::Map;
-use std::{ffi::OsStr, fs, io, path::PathBuf, slice::Iter};
+use crate::{
+    map::{Format, Map, MapError},
+    sim::Rules,
+    tiled,
+};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    slice::Iter,
+};
 
 #[derive(Debug)]
 pub struct LevelManager {
     paths: Vec<PathBuf>,
     current: usize,
+    /// Level being test played from the editor instead of the current level.
+    test: Option<(String, Map)>,
+}
+
+/// Directory the installed levels are read from.
+pub fn directory() -> PathBuf {
+    let mut path = PathBuf::from("assets");
+    path.push("levels");
+    path
 }
 
 impl LevelManager {
     pub fn fetch() -> io::Result<Self> {
         let mut paths = Vec::new();
-        let mut level_path = PathBuf::from("assets");
-        level_path.push("levels");
+        let level_path = directory();
         if level_path.exists() {
             for level in fs::read_dir(level_path)?.flatten() {
                 let path = level.path();
-                if path.extension() == Some(OsStr::new("csv")) {
+                if Format::from_path(&path).is_some() || tiled::is_tiled(&path) {
                     paths.push(path);
                 }
             }
-            paths.sort();
+            // Numbered levels are sorted by number, so that "10" comes after "2".
+            paths.sort_by_cached_key(|path| {
+                let name = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+                let number = name
+                    .chars()
+                    .take_while(char::is_ascii_digit)
+                    .collect::<String>()
+                    .parse()
+                    .ok();
+                let order = metadata(path).ok().and_then(|metadata| metadata.order);
+                (order.or(number).unwrap_or(usize::MAX), name.to_string())
+            });
         }
-        Ok(LevelManager { paths, current: 0 })
+        Ok(LevelManager {
+            paths,
+            current: 0,
+            test: None,
+        })
     }
 
     pub fn set_level(&mut self, index: usize) {
         self.current = index;
     }
 
+    /// Index of the level with the given name.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.paths
+            .iter()
+            .position(|path| path.file_stem().and_then(OsStr::to_str) == Some(name))
+    }
+
+    /// Play `map` under the given name instead of the current level, until testing stops.
+    pub fn start_test(&mut self, name: String, map: Map) {
+        self.test = Some((name, map));
+    }
+
+    pub fn stop_test(&mut self) {
+        self.test = None;
+    }
+
+    /// Whether a level from the editor is being test played.
+    pub fn is_testing(&self) -> bool {
+        self.test.is_some()
+    }
+
+    pub fn has_next(&self) -> bool {
+        !self.is_testing() && self.current + 1 < self.paths.len()
+    }
+
+    /// Advance to the next level, returning whether there was one.
+    pub fn next(&mut self) -> bool {
+        if self.has_next() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Name of the current level, taken from its file name.
+    pub fn current_name(&self) -> Option<&str> {
+        if let Some((name, _)) = &self.test {
+            return Some(name);
+        }
+        self.paths[self.current].file_stem()?.to_str()
+    }
+
     pub fn iter(&self) -> Iter<'_, PathBuf> {
         self.paths.iter()
     }
@@ -36,7 +111,83 @@ impl LevelManager {
         self.paths.len()
     }
 
-    pub fn load(&self) -> io::Result<Map> {
+    pub fn load(&self) -> Result<Map, MapError> {
+        if let Some((name, map)) = &self.test {
+            map.validate(Path::new(name))?;
+            return Ok(map.clone());
+        }
         Map::load(&self.paths[self.current])
     }
+
+    pub fn metadata(&self) -> Result<Metadata, MapError> {
+        if self.is_testing() {
+            return Ok(Metadata::default());
+        }
+        metadata(&self.paths[self.current])
+    }
+}
+
+/// Information about a level, read from the `.meta` file next to it.
+#[derive(Debug, Default, Clone)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub difficulty: Option<String>,
+    /// Number of moves a good run should take.
+    pub par: Option<usize>,
+    /// Position of the level in the level select menu.
+    pub order: Option<usize>,
+    pub rules: Rules,
+}
+
+impl Metadata {
+    /// Title of the level, falling back to the name of its file.
+    pub fn title_or<'a>(&'a self, name: &'a str) -> &'a str {
+        self.title.as_deref().unwrap_or(name)
+    }
+}
+
+/// Read the metadata of the level at `path`, which is empty if the level has no `.meta` file.
+///
+/// Every line of the file is a `key: value` pair, and empty lines and lines starting with `#` are
+/// ignored.
+pub fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata, MapError> {
+    let path = path.as_ref().with_extension("meta");
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Metadata::default()),
+        Err(source) => return Err(MapError::Io { path, source }),
+    };
+    let mut metadata = Metadata::default();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message| MapError::Metadata {
+            path: path.clone(),
+            row: i + 1,
+            message,
+        };
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid(format!("expected `key: value`, found `{}`", line)))?;
+        let (key, value) = (key.trim(), value.trim());
+        let invalid_value = || invalid(format!("`{}` is not a valid value for `{}`", value, key));
+        let number = || value.parse().map_err(|_| invalid_value());
+        let flag = || value.parse().map_err(|_| invalid_value());
+        match key {
+            "title" => metadata.title = Some(value.to_string()),
+            "author" => metadata.author = Some(value.to_string()),
+            "description" => metadata.description = Some(value.to_string()),
+            "difficulty" => metadata.difficulty = Some(value.to_string()),
+            "par" => metadata.par = Some(number()?),
+            "order" => metadata.order = Some(number()?),
+            "enemies_teleport" => metadata.rules.enemies_teleport = flag()?,
+            "hazards_remove_enemies" => metadata.rules.hazards_remove_enemies = flag()?,
+            _ => return Err(invalid(format!("unknown key `{}`", key))),
+        }
+    }
+    Ok(metadata)
 }
@@ -1,50 +1,97 @@
+mod check;
+mod crates;
+mod editor;
 mod enemy;
+mod history;
 mod level;
 mod map;
 mod player;
+mod replay;
 mod respawn;
+mod save;
+mod sim;
+mod solver;
+mod tiled;
 mod ui;
 
-use bevy::{prelude::*, render::camera::Camera};
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+use check::PendingCheck;
+use crates::Crate;
+use editor::Editor;
 use enemy::Enemy;
+use history::History;
 use level::LevelManager;
-use map::{Coordinates, Tile};
-use player::{Player, PlayerMovementEvent};
-use respawn::{RespawnEvent, RespawnPoint};
-use std::error::Error;
-use ui::{LevelButton, PlayButton};
+use map::{Coordinates, Direction, Map, Tile};
+use player::{ActionEvent, ElapsedTime, MoveCount, Player, PlayerMovementEvent};
+use replay::{Playback, Replay};
+use respawn::{RespawnCount, RespawnEvent};
+use save::Progress;
+use sim::GameState;
+use solver::Unsolved;
+use std::{env, error::Error, path::PathBuf, process, sync::atomic::AtomicBool};
+use ui::{BackButton, CompletionButton, LevelButton, LevelError, MenuButton};
 
 pub const TILE_SIZE: f32 = 64.0;
 pub const BACKGROUND_COLOR: Color = Color::rgb(0.18, 0.2, 0.25);
+const ARROW_COLOR: Color = Color::rgb(0.53, 0.58, 0.66);
+const CLOSED_GOAL_COLOR: Color = Color::rgb(0.5, 0.45, 0.36);
+
+/// Marks the camera that follows the player, as opposed to the UI camera.
+#[derive(Component, Debug)]
+pub struct GameCamera;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum MenuState {
     Main,
     Level,
+    Error,
+}
+
+/// Parts of a turn while playing, which must run in order for replays to be deterministic.
+#[derive(SystemLabel, Debug, Clone, Eq, PartialEq, Hash)]
+enum TurnSystem {
+    /// Turning key presses or replayed actions into action events.
+    Input,
+    /// Applying the actions to the game state.
+    Actions,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
     Menu(MenuState),
     InGame,
+    LevelComplete,
+    Editor,
 }
 
 fn update_app_state(
     mut state: ResMut<State<AppState>>,
     mut level_manager: ResMut<LevelManager>,
-    play_interaction_query: Query<&Interaction, (Changed<Interaction>, With<PlayButton>)>,
+    menu_interaction_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
     level_interaction_query: Query<(&Interaction, &LevelButton), Changed<Interaction>>,
+    back_interaction_query: Query<&Interaction, (Changed<Interaction>, With<BackButton>)>,
+    completion_interaction_query: Query<(&Interaction, &CompletionButton), Changed<Interaction>>,
     mut keyboard_input: ResMut<Input<KeyCode>>,
 ) {
     match state.current() {
         AppState::Menu(MenuState::Main) => {
-            if let Ok(Interaction::Clicked) = play_interaction_query.get_single() {
-                state.set(AppState::Menu(MenuState::Level)).unwrap();
+            for (interaction, button) in menu_interaction_query.iter() {
+                if matches!(interaction, Interaction::Clicked) {
+                    match button {
+                        MenuButton::Play => state.set(AppState::Menu(MenuState::Level)).unwrap(),
+                        MenuButton::Editor => state.set(AppState::Editor).unwrap(),
+                    }
+                }
             }
         }
         AppState::InGame | AppState::Menu(MenuState::Level) => {
             if keyboard_input.pressed(KeyCode::Escape) {
-                state.set(AppState::Menu(MenuState::Main)).unwrap();
+                if level_manager.is_testing() {
+                    level_manager.stop_test();
+                    state.set(AppState::Editor).unwrap();
+                } else {
+                    state.set(AppState::Menu(MenuState::Main)).unwrap();
+                }
                 keyboard_input.reset(KeyCode::Escape);
             }
             for (interaction, level_button) in level_interaction_query.iter() {
@@ -54,87 +101,168 @@ fn update_app_state(
                 }
             }
         }
+        AppState::Menu(MenuState::Error) => {
+            let back_clicked = matches!(
+                back_interaction_query.get_single(),
+                Ok(Interaction::Clicked)
+            );
+            if back_clicked || keyboard_input.pressed(KeyCode::Escape) {
+                state.set(AppState::Menu(MenuState::Level)).unwrap();
+                keyboard_input.reset(KeyCode::Escape);
+            }
+        }
+        AppState::LevelComplete => {
+            if keyboard_input.pressed(KeyCode::Escape) {
+                if level_manager.is_testing() {
+                    level_manager.stop_test();
+                    state.set(AppState::Editor).unwrap();
+                } else {
+                    state.set(AppState::Menu(MenuState::Main)).unwrap();
+                }
+                keyboard_input.reset(KeyCode::Escape);
+            }
+            for (interaction, button) in completion_interaction_query.iter() {
+                if matches!(interaction, Interaction::Clicked) {
+                    match button {
+                        CompletionButton::Next => {
+                            if level_manager.next() {
+                                state.set(AppState::InGame).unwrap();
+                            }
+                        }
+                        CompletionButton::Retry => state.set(AppState::InGame).unwrap(),
+                        CompletionButton::LevelSelect => {
+                            state.set(AppState::Menu(MenuState::Level)).unwrap()
+                        }
+                        CompletionButton::Editor => {
+                            level_manager.stop_test();
+                            state.set(AppState::Editor).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+        AppState::Editor => {
+            if keyboard_input.pressed(KeyCode::Escape) {
+                state.set(AppState::Menu(MenuState::Main)).unwrap();
+                keyboard_input.reset(KeyCode::Escape);
+            }
+        }
     }
 }
 
-fn delta_from_code(code: KeyCode) -> (i8, i8) {
-    let delta_x: i8 = match code {
-        KeyCode::A | KeyCode::Left => -1,
-        KeyCode::D | KeyCode::Right => 1,
-        _ => 0,
-    };
-    let delta_y: i8 = match code {
-        KeyCode::W | KeyCode::Up => -1,
-        KeyCode::S | KeyCode::Down => 1,
-        _ => 0,
+fn direction_from_code(code: KeyCode) -> Option<Direction> {
+    match code {
+        KeyCode::W | KeyCode::Up => Some(Direction::Up),
+        KeyCode::A | KeyCode::Left => Some(Direction::Left),
+        KeyCode::S | KeyCode::Down => Some(Direction::Down),
+        KeyCode::D | KeyCode::Right => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+/// Marker drawn on the edge of a one-way tile that it must be entered towards.
+pub fn arrow_marker(arrow: Direction) -> SpriteBundle {
+    let offset = match arrow {
+        Direction::Up => Vec3::new(0.0, 0.375, 1.0),
+        Direction::Down => Vec3::new(0.0, -0.375, 1.0),
+        Direction::Left => Vec3::new(-0.375, 0.0, 1.0),
+        Direction::Right => Vec3::new(0.375, 0.0, 1.0),
     };
-    (delta_x, delta_y)
+    SpriteBundle {
+        sprite: Sprite {
+            custom_size: Some(Vec2::splat(TILE_SIZE / 4.0)),
+            color: ARROW_COLOR,
+            ..Sprite::default()
+        },
+        transform: Transform::from_translation(offset * TILE_SIZE),
+        ..SpriteBundle::default()
+    }
 }
 
-fn game_setup(mut commands: Commands, level_manager: Res<LevelManager>) {
-    let map = level_manager.load().unwrap();
+fn game_setup(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut level_error: ResMut<LevelError>,
+    level_manager: Res<LevelManager>,
+    pool: Res<AsyncComputeTaskPool>,
+) {
+    let level = level_manager
+        .load()
+        .and_then(|map| Ok((map, level_manager.metadata()?)));
+    let (game_state, metadata) = match level {
+        Ok((map, metadata)) => (GameState::new(map, metadata.rules), metadata),
+        Err(err) => {
+            // Show the error instead of starting the level.
+            level_error.0 = err.to_string();
+            state.set(AppState::Menu(MenuState::Error)).unwrap();
+            return;
+        }
+    };
+    let map = game_state.map();
     let mut camera = OrthographicCameraBundle::new_2d();
 
-    let half_size = (map.size / 2) as isize;
-
-    let mut player_transform = Transform::default();
-    let mut player_coordinates = Coordinates::new(half_size as usize, half_size as usize);
-
     let tile_size = Vec2::splat(TILE_SIZE);
 
-    for y in 0..map.size {
-        for x in 0..map.size {
-            let position = Vec2::new(
-                (x as isize - half_size) as f32,
-                (half_size - y as isize) as f32,
-            );
-            let coordinates = Coordinates::new(x, y);
-            let transform = Transform::from_translation((position * tile_size).extend(0.0));
-            let tile = map[y][x];
-            match tile {
-                Tile::Start => {
-                    // Specify player's initial translation.
-                    player_transform = transform;
-                    player_coordinates = coordinates;
-                }
-                Tile::Goal => {
-                    // Make the initial camera's position equal to the goal tile.
-                    camera.transform = transform;
-                }
-                Tile::Enemy => {
-                    // Spawn enemy.
-                    commands
-                        .spawn_bundle(SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(tile_size),
-                                color: Enemy::COLOR,
-                                ..Sprite::default()
-                            },
-                            transform,
-                            ..SpriteBundle::default()
-                        })
-                        .insert(coordinates)
-                        .insert(RespawnPoint::new(transform.translation, coordinates))
-                        .insert(Enemy);
-                }
-                _ => {}
-            }
-            commands
-                .spawn_bundle(SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(tile_size),
-                        color: tile.color(),
-                        ..Sprite::default()
-                    },
-                    transform,
-                    ..SpriteBundle::default()
-                })
-                .insert(coordinates)
-                .insert(tile);
+    for (coordinates, tile) in map.tiles() {
+        let translation = map.translation(coordinates);
+        if let Tile::Goal = tile {
+            // Make the initial camera's position equal to the goal tile.
+            camera.transform = Transform::from_translation(translation);
         }
+        // Tiles sit below the arrows drawn on them, which sit below the player and enemies.
+        let transform = Transform::from_translation(translation - Vec3::new(0.0, 0.0, 2.0));
+        let mut entity = commands.spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(tile_size),
+                color: tile.color(),
+                ..Sprite::default()
+            },
+            transform,
+            ..SpriteBundle::default()
+        });
+        entity.insert(coordinates).insert(tile);
+        if let Some(arrow) = tile.arrow() {
+            entity.with_children(|parent| {
+                parent.spawn_bundle(arrow_marker(arrow));
+            });
+        }
+    }
+
+    // Spawn enemies.
+    for (i, enemy) in game_state.enemies().iter().enumerate() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(tile_size),
+                    color: enemy.kind.color(enemy.awake),
+                    ..Sprite::default()
+                },
+                transform: Transform::from_translation(map.translation(enemy.coordinates)),
+                ..SpriteBundle::default()
+            })
+            .insert(enemy.coordinates)
+            .insert(enemy.kind)
+            .insert(Enemy::new(i));
+    }
+
+    // Spawn crates.
+    for (i, coordinates) in game_state.crates().iter().enumerate() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(tile_size),
+                    color: Crate::COLOR,
+                    ..Sprite::default()
+                },
+                transform: Transform::from_translation(map.translation(*coordinates)),
+                ..SpriteBundle::default()
+            })
+            .insert(*coordinates)
+            .insert(Crate::new(i));
     }
 
     // Spawn player.
+    let player_coordinates = game_state.player();
     commands
         .spawn_bundle(SpriteBundle {
             sprite: Sprite {
@@ -142,24 +270,50 @@ fn game_setup(mut commands: Commands, level_manager: Res<LevelManager>) {
                 color: Player::COLOR,
                 ..Sprite::default()
             },
-            transform: player_transform,
+            transform: Transform::from_translation(map.translation(player_coordinates)),
             ..SpriteBundle::default()
         })
         .insert(player_coordinates)
-        .insert(RespawnPoint::new(
-            player_transform.translation,
-            player_coordinates,
-        ))
         .insert(Player);
 
-    commands.spawn_bundle(camera);
+    commands.spawn_bundle(camera).insert(GameCamera);
 
-    commands.insert_resource(map);
+    // Warn about levels that cannot be won, such as custom levels that were never checked.
+    commands.insert_resource(PendingCheck::spawn(&pool, game_state.clone()));
+    commands.insert_resource(game_state);
+    commands.insert_resource(History::default());
+    commands.insert_resource(RespawnCount::default());
+    commands.insert_resource(MoveCount {
+        moves: 0,
+        par: metadata.par,
+    });
+    commands.insert_resource(ElapsedTime::default());
+    // Kept until the next level starts, so that a winning run can be saved from the complete screen.
+    commands.insert_resource(Replay::new(
+        level_manager.current_name().unwrap_or_default().to_string(),
+    ));
+}
+
+/// Keep tile sprites in line with the tiles changed during the current attempt.
+fn tile_sync(
+    game_state: Res<GameState>,
+    mut tile_query: Query<(&Coordinates, &mut Tile, &mut Sprite)>,
+) {
+    if game_state.is_changed() {
+        let (collected, total) = game_state.gems();
+        for (coordinates, mut tile, mut sprite) in tile_query.iter_mut() {
+            *tile = game_state.map()[coordinates.y()][coordinates.x()];
+            sprite.color = match *tile {
+                Tile::Goal if collected < total => CLOSED_GOAL_COLOR,
+                tile => tile.color(),
+            };
+        }
+    }
 }
 
 fn camera_follow(
-    mut camera_query: Query<&mut Transform, With<Camera>>,
-    player_query: Query<&Transform, (With<Player>, Without<Camera>)>,
+    mut camera_query: Query<&mut Transform, With<GameCamera>>,
+    player_query: Query<&Transform, (With<Player>, Without<GameCamera>)>,
 ) {
     let mut camera_transform = camera_query.single_mut();
     let player_transform = player_query.single();
@@ -174,14 +328,126 @@ fn cleanup(mut commands: Commands, query: Query<Entity>) {
     }
 }
 
+/// Print the shortest solution of the level at `path`, exiting with an error if there is none.
+fn solve_level(path: &str) -> Result<(), Box<dyn Error>> {
+    let game_state = GameState::new(Map::load(path)?, level::metadata(path)?.rules);
+    match solver::solve(&game_state, solver::MAX_STATES, &AtomicBool::new(false)) {
+        Ok(moves) => {
+            let moves: Vec<String> = moves.iter().map(Direction::to_string).collect();
+            println!("{}: solved in {} moves", path, moves.len());
+            println!("{}", moves.join(" "));
+            Ok(())
+        }
+        Err(Unsolved::Unwinnable) => {
+            eprintln!("{}: no solution", path);
+            process::exit(1);
+        }
+        // Nothing cancels the search from the command line.
+        Err(Unsolved::GaveUp | Unsolved::Cancelled) => {
+            eprintln!(
+                "{}: gave up after searching {} states",
+                path,
+                solver::MAX_STATES
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Report problems in the given levels, or every installed level if none are given.
+///
+/// Exits with an error if any level has a problem.
+fn check_levels(paths: &[String]) -> Result<(), Box<dyn Error>> {
+    let paths: Vec<PathBuf> = if paths.is_empty() {
+        LevelManager::fetch()?.iter().cloned().collect()
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let mut failed = false;
+    for path in paths.iter() {
+        match check::check(path) {
+            Ok(report) if report.problems.is_empty() => {
+                if let Some(moves) = report.solution {
+                    match report.par {
+                        Some(par) => println!(
+                            "{}: ok, solved in {} moves (par {})",
+                            path.display(),
+                            moves,
+                            par
+                        ),
+                        None => println!("{}: ok, solved in {} moves", path.display(), moves),
+                    }
+                }
+            }
+            Ok(report) => {
+                failed = true;
+                for problem in report.problems.iter() {
+                    eprintln!("{}", problem);
+                }
+            }
+            Err(err) => {
+                failed = true;
+                eprintln!("{}", err);
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Select the level of the replay at `path` and prepare it for playback.
+fn load_replay(
+    level_manager: &mut LevelManager,
+    path: &str,
+    speed: Option<&String>,
+) -> Result<Playback, Box<dyn Error>> {
+    let replay = Replay::load(path)?;
+    let level = level_manager
+        .find(&replay.level)
+        .ok_or_else(|| format!("{}: unknown level `{}`", path, replay.level))?;
+    level_manager.set_level(level);
+    let speed = match speed {
+        Some(speed) => speed.parse()?,
+        None => replay::DEFAULT_SPEED,
+    };
+    // Each action waits `1 / speed` seconds, which must be a valid duration.
+    if speed <= 0.0 || !speed.is_finite() || !(1.0 / speed).is_finite() {
+        return Err(format!("replay speed must be a positive number, found `{}`", speed).into());
+    }
+    Ok(Playback::new(replay, speed))
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let level_manager = LevelManager::fetch()?;
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_state(AppState::Menu(MenuState::Main))
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut level_manager = LevelManager::fetch()?;
+    let mut initial_state = AppState::Menu(MenuState::Main);
+    let mut playback = None;
+    match args.split_first() {
+        Some((flag, [path])) if flag == "--solve" => return solve_level(path),
+        Some((flag, paths)) if flag == "--check" => return check_levels(paths),
+        Some((flag, [path, speed @ ..])) if flag == "--replay" && speed.len() <= 1 => {
+            playback = Some(load_replay(&mut level_manager, path, speed.first())?);
+            initial_state = AppState::InGame;
+        }
+        _ => {}
+    }
+    let progress = Progress::load();
+    let mut app = App::new();
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+    app.add_plugins(DefaultPlugins)
+        .add_state(initial_state)
         .add_event::<RespawnEvent>()
         .add_event::<PlayerMovementEvent>()
+        .add_event::<ActionEvent>()
+        .insert_resource(ClearColor(BACKGROUND_COLOR))
         .insert_resource(level_manager)
+        .insert_resource(progress)
+        .init_resource::<LevelError>()
+        .init_resource::<Editor>()
         .add_system(update_app_state.system())
         // Menu state.
         .add_system_set(
@@ -207,18 +473,88 @@ fn main() -> Result<(), Box<dyn Error>> {
         .add_system_set(
             SystemSet::on_exit(AppState::Menu(MenuState::Level)).with_system(cleanup.system()),
         )
+        // Error state.
+        .add_system_set(
+            SystemSet::on_enter(AppState::Menu(MenuState::Error))
+                .with_system(ui::error_menu_setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Menu(MenuState::Error))
+                .with_system(ui::button_system.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Menu(MenuState::Error)).with_system(cleanup.system()),
+        )
+        // LevelComplete state.
+        .add_system_set(
+            SystemSet::on_enter(AppState::LevelComplete)
+                .with_system(ui::complete_menu_setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelComplete)
+                .with_system(ui::button_system.system())
+                .with_system(replay::save_replay.system()),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::LevelComplete).with_system(cleanup.system()))
+        // Editor state.
+        .add_system_set(
+            SystemSet::on_enter(AppState::Editor)
+                .with_system(editor::editor_setup.system())
+                .with_system(ui::editor_hud_setup.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Editor)
+                .with_system(editor::editor_input.system())
+                .with_system(editor::editor_save.system())
+                .with_system(editor::editor_check.system())
+                .with_system(editor::editor_render.system())
+                .with_system(ui::editor_hud_update.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Editor)
+                .with_system(cleanup.system())
+                .with_system(check::stop_check.system()),
+        )
         // InGame state.
-        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(game_setup.system()))
+        .add_system_set(
+            SystemSet::on_enter(AppState::InGame)
+                .with_system(game_setup.system())
+                .with_system(ui::hud_setup.system()),
+        )
         .add_system_set(
             SystemSet::on_update(AppState::InGame)
-                .with_system(player::player_movement.system())
-                .with_system(player::check_completion.system())
-                .with_system(enemy::enemy_movement.system())
-                .with_system(respawn::respawn_check.system())
-                .with_system(respawn::respawn_event_listener.system())
+                .with_system(player::player_input.system().label(TurnSystem::Input))
+                .with_system(replay::play_actions.system().label(TurnSystem::Input))
+                .with_system(replay::record_actions.system().after(TurnSystem::Input))
+                .with_system(
+                    player::apply_actions
+                        .system()
+                        .label(TurnSystem::Actions)
+                        .after(TurnSystem::Input),
+                )
+                .with_system(player::check_completion.system().after(TurnSystem::Actions))
+                .with_system(save::record_completion.system().after(TurnSystem::Actions))
+                .with_system(
+                    respawn::respawn_event_listener
+                        .system()
+                        .after(TurnSystem::Actions),
+                )
+                .with_system(player::player_sync.system().after(TurnSystem::Actions))
+                .with_system(enemy::enemy_sync.system().after(TurnSystem::Actions))
+                .with_system(tile_sync.system().after(TurnSystem::Actions))
+                .with_system(crates::crate_sync.system().after(TurnSystem::Actions))
+                .with_system(ui::hud_update.system().after(TurnSystem::Actions))
+                .with_system(ui::hud_check_update.system())
+                .with_system(replay::save_replay.system())
+                .with_system(player::tick_elapsed_time.system())
                 .with_system(camera_follow.system()),
         )
-        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(cleanup.system()))
+        .add_system_set(
+            SystemSet::on_exit(AppState::InGame)
+                .with_system(cleanup.system())
+                .with_system(replay::stop_playback.system())
+                .with_system(check::stop_check.system()),
+        )
         .run();
     Ok(())
 }
@@ -1,14 +1,55 @@
+use crate::{enemy::EnemyKind, tiled};
 use bevy::{ecs::component::Component, math::Vec3, render::color::Color};
 use num_enum::TryFromPrimitive;
 use std::{
     convert::TryFrom,
+    error::Error,
+    fmt,
     fs::File,
     io::{self, BufRead, BufReader},
     ops::Index,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Coordinates {
     x: usize,
     y: usize,
@@ -27,36 +68,50 @@ impl Coordinates {
         self.y
     }
 
-    pub fn move_up(&mut self, translation: &mut Vec3, map: &Map) {
-        if self.y > 0 && !map[self.y - 1][self.x].is_solid() {
+    pub fn move_up(&mut self, map: &Map) -> bool {
+        if self.y > 0 && map[self.y - 1][self.x].can_enter(Direction::Up) {
             self.y -= 1;
-            translation.y += crate::TILE_SIZE;
+            return true;
         }
+        false
     }
 
-    pub fn move_down(&mut self, translation: &mut Vec3, map: &Map) {
-        if self.y < map.size - 1 && !map[self.y + 1][self.x].is_solid() {
+    pub fn move_down(&mut self, map: &Map) -> bool {
+        if self.y < map.height - 1 && map[self.y + 1][self.x].can_enter(Direction::Down) {
             self.y += 1;
-            translation.y -= crate::TILE_SIZE;
+            return true;
         }
+        false
     }
 
-    pub fn move_left(&mut self, translation: &mut Vec3, map: &Map) {
-        if self.x > 0 && !map[self.y][self.x - 1].is_solid() {
+    pub fn move_left(&mut self, map: &Map) -> bool {
+        if self.x > 0 && map[self.y][self.x - 1].can_enter(Direction::Left) {
             self.x -= 1;
-            translation.x -= crate::TILE_SIZE;
+            return true;
         }
+        false
     }
 
-    pub fn move_right(&mut self, translation: &mut Vec3, map: &Map) {
-        if self.x < map.size - 1 && !map[self.y][self.x + 1].is_solid() {
+    pub fn move_right(&mut self, map: &Map) -> bool {
+        if self.x < map.width - 1 && map[self.y][self.x + 1].can_enter(Direction::Right) {
             self.x += 1;
-            translation.x += crate::TILE_SIZE;
+            return true;
+        }
+        false
+    }
+
+    /// Move one tile in the given direction, returning whether the move succeeded.
+    pub fn move_in(&mut self, direction: Direction, map: &Map) -> bool {
+        match direction {
+            Direction::Up => self.move_up(map),
+            Direction::Down => self.move_down(map),
+            Direction::Left => self.move_left(map),
+            Direction::Right => self.move_right(map),
         }
     }
 }
 
-#[derive(Component, Debug, Clone, Copy, TryFromPrimitive)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Tile {
     Empty,
@@ -64,54 +119,514 @@ pub enum Tile {
     Start,
     Goal,
     Enemy,
+    VerticalEnemy,
+    MirrorEnemy,
+    HorizontalPatrolEnemy,
+    VerticalPatrolEnemy,
+    SleeperEnemy,
+    FastEnemy,
+    HunterEnemy,
+    RedKey,
+    RedDoor,
+    GreenKey,
+    GreenDoor,
+    BlueKey,
+    BlueDoor,
+    Switch,
+    Gate,
+    OpenGate,
+    UpArrow,
+    DownArrow,
+    LeftArrow,
+    RightArrow,
+    CrumblingFloor,
+    OrangeTeleporter,
+    PurpleTeleporter,
+    CyanTeleporter,
+    Spikes,
+    Pit,
+    Crate,
+    Gem,
 }
 
 impl Tile {
+    /// Number of tile values, which run from 0 up to the last variant.
+    pub const COUNT: u8 = Tile::Gem as u8 + 1;
+
     pub fn color(&self) -> Color {
         match self {
             Tile::Block => Color::rgb(0.71, 0.56, 0.68),
             Tile::Start => Color::rgb(0.64, 0.75, 0.55),
             Tile::Goal => Color::rgb(0.92, 0.8, 0.55),
-            Tile::Empty | Tile::Enemy => crate::BACKGROUND_COLOR,
+            Tile::RedKey => Color::rgb(0.89, 0.45, 0.4),
+            Tile::RedDoor => Color::rgb(0.58, 0.3, 0.3),
+            Tile::GreenKey => Color::rgb(0.49, 0.8, 0.45),
+            Tile::GreenDoor => Color::rgb(0.3, 0.5, 0.32),
+            Tile::BlueKey => Color::rgb(0.42, 0.58, 0.92),
+            Tile::BlueDoor => Color::rgb(0.28, 0.36, 0.6),
+            Tile::Switch => Color::rgb(0.6, 0.64, 0.7),
+            Tile::Gate => Color::rgb(0.48, 0.5, 0.56),
+            Tile::OpenGate => Color::rgb(0.27, 0.29, 0.35),
+            Tile::UpArrow | Tile::DownArrow | Tile::LeftArrow | Tile::RightArrow => {
+                Color::rgb(0.25, 0.28, 0.35)
+            }
+            Tile::CrumblingFloor => Color::rgb(0.42, 0.37, 0.33),
+            Tile::OrangeTeleporter => Color::rgb(0.95, 0.6, 0.3),
+            Tile::PurpleTeleporter => Color::rgb(0.62, 0.45, 0.88),
+            Tile::CyanTeleporter => Color::rgb(0.35, 0.8, 0.82),
+            Tile::Spikes => Color::rgb(0.55, 0.56, 0.6),
+            Tile::Pit => Color::rgb(0.08, 0.09, 0.12),
+            Tile::Gem => Color::rgb(0.4, 0.86, 0.72),
+            _ => crate::BACKGROUND_COLOR,
+        }
+    }
+
+    /// Kind of the enemy that spawns on this tile, if any.
+    pub fn enemy_kind(&self) -> Option<EnemyKind> {
+        match self {
+            Tile::Enemy => Some(EnemyKind::Chaser),
+            Tile::VerticalEnemy => Some(EnemyKind::VerticalChaser),
+            Tile::MirrorEnemy => Some(EnemyKind::Mirror),
+            Tile::HorizontalPatrolEnemy => Some(EnemyKind::Patroller(Direction::Right)),
+            Tile::VerticalPatrolEnemy => Some(EnemyKind::Patroller(Direction::Down)),
+            Tile::SleeperEnemy => Some(EnemyKind::Sleeper),
+            Tile::FastEnemy => Some(EnemyKind::Fast),
+            Tile::HunterEnemy => Some(EnemyKind::Hunter),
+            _ => None,
+        }
+    }
+
+    /// Door opened by the key on this tile, if any.
+    pub fn door(&self) -> Option<Tile> {
+        match self {
+            Tile::RedKey => Some(Tile::RedDoor),
+            Tile::GreenKey => Some(Tile::GreenDoor),
+            Tile::BlueKey => Some(Tile::BlueDoor),
+            _ => None,
         }
     }
 
+    /// The only direction this tile can be entered in, if it is a one-way tile.
+    pub fn arrow(&self) -> Option<Direction> {
+        match self {
+            Tile::UpArrow => Some(Direction::Up),
+            Tile::DownArrow => Some(Direction::Down),
+            Tile::LeftArrow => Some(Direction::Left),
+            Tile::RightArrow => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    /// Tile written as the given character in ASCII levels.
+    pub fn from_char(character: char) -> Option<Self> {
+        let tile = match character {
+            '.' => Tile::Empty,
+            '#' => Tile::Block,
+            'S' => Tile::Start,
+            'G' => Tile::Goal,
+            'E' => Tile::Enemy,
+            'V' => Tile::VerticalEnemy,
+            'M' => Tile::MirrorEnemy,
+            'H' => Tile::HorizontalPatrolEnemy,
+            'I' => Tile::VerticalPatrolEnemy,
+            'Z' => Tile::SleeperEnemy,
+            'F' => Tile::FastEnemy,
+            'X' => Tile::HunterEnemy,
+            'a' => Tile::RedKey,
+            'A' => Tile::RedDoor,
+            'b' => Tile::GreenKey,
+            'B' => Tile::GreenDoor,
+            'c' => Tile::BlueKey,
+            'C' => Tile::BlueDoor,
+            'o' => Tile::Switch,
+            '=' => Tile::Gate,
+            '_' => Tile::OpenGate,
+            '^' => Tile::UpArrow,
+            'v' => Tile::DownArrow,
+            '<' => Tile::LeftArrow,
+            '>' => Tile::RightArrow,
+            '~' => Tile::CrumblingFloor,
+            '1' => Tile::OrangeTeleporter,
+            '2' => Tile::PurpleTeleporter,
+            '3' => Tile::CyanTeleporter,
+            'x' => Tile::Spikes,
+            'O' => Tile::Pit,
+            '$' => Tile::Crate,
+            '*' => Tile::Gem,
+            _ => return None,
+        };
+        Some(tile)
+    }
+
+    pub fn is_teleporter(&self) -> bool {
+        matches!(
+            self,
+            Tile::OrangeTeleporter | Tile::PurpleTeleporter | Tile::CyanTeleporter
+        )
+    }
+
+    /// Whether stepping onto this tile kills the player or removes an enemy.
+    pub fn is_hazard(&self) -> bool {
+        matches!(self, Tile::Spikes | Tile::Pit)
+    }
+
     pub fn is_solid(&self) -> bool {
-        matches!(self, Tile::Block)
+        matches!(
+            self,
+            Tile::Block | Tile::Gate | Tile::RedDoor | Tile::GreenDoor | Tile::BlueDoor
+        )
+    }
+
+    /// Whether something moving in the given direction can step onto this tile.
+    pub fn can_enter(&self, direction: Direction) -> bool {
+        match self.arrow() {
+            Some(arrow) => arrow == direction,
+            None => !self.is_solid(),
+        }
     }
 }
 
-#[derive(Debug)]
+/// The text formats levels can be written in, told apart by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Comma separated tile values, in `.csv` files.
+    Csv,
+    /// One character per tile, in `.txt` files.
+    Ascii,
+}
+
+/// Why a cell of a level could not be read as a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellError {
+    NotANumber,
+    UnknownTile,
+}
+
+impl Format {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "csv" => Some(Format::Csv),
+            "txt" => Some(Format::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Split a row into its cells, each with its 1-based column.
+    pub fn cells(self, row: &str) -> Vec<(usize, &str)> {
+        match self {
+            Format::Csv => {
+                let mut column = 1;
+                row.split(',')
+                    .map(|value| {
+                        let cell = (column, value);
+                        column += value.chars().count() + 1;
+                        cell
+                    })
+                    .collect()
+            }
+            Format::Ascii => row
+                .char_indices()
+                .enumerate()
+                .map(|(i, (start, character))| (i + 1, &row[start..start + character.len_utf8()]))
+                .collect(),
+        }
+    }
+
+    pub fn parse(self, cell: &str) -> Result<Tile, CellError> {
+        match self {
+            Format::Csv => {
+                let number = cell.parse::<u8>().map_err(|_| CellError::NotANumber)?;
+                Tile::try_from(number).map_err(|_| CellError::UnknownTile)
+            }
+            Format::Ascii => cell
+                .chars()
+                .next()
+                .and_then(Tile::from_char)
+                .ok_or(CellError::UnknownTile),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Map {
-    pub size: usize,
+    pub width: usize,
+    pub height: usize,
     layout: Vec<Tile>,
 }
 
+/// An error encountered while loading a level, with 1-based rows and columns.
+#[derive(Debug)]
+pub enum MapError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    NotANumber {
+        path: PathBuf,
+        row: usize,
+        column: usize,
+        text: String,
+    },
+    UnknownTile {
+        path: PathBuf,
+        row: usize,
+        column: usize,
+        text: String,
+    },
+    ColumnCount {
+        path: PathBuf,
+        row: usize,
+        text: String,
+        expected: usize,
+        found: usize,
+    },
+    Metadata {
+        path: PathBuf,
+        row: usize,
+        message: String,
+    },
+    Tiled {
+        path: PathBuf,
+        message: String,
+    },
+    /// The level has no tiles at all.
+    Empty {
+        path: PathBuf,
+    },
+    /// The level has no tile of a kind it cannot be played without.
+    MissingTile {
+        path: PathBuf,
+        tile: Tile,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            MapError::NotANumber {
+                path,
+                row,
+                column,
+                text,
+            } => write!(
+                f,
+                "{}:{}:{}: `{}` is not a tile value",
+                path.display(),
+                row,
+                column,
+                text
+            ),
+            MapError::UnknownTile {
+                path,
+                row,
+                column,
+                text,
+            } => write!(
+                f,
+                "{}:{}:{}: unknown tile `{}`",
+                path.display(),
+                row,
+                column,
+                text
+            ),
+            MapError::ColumnCount {
+                path,
+                row,
+                text,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}:1: row `{}` has {} columns but the first row has {}",
+                path.display(),
+                row,
+                text,
+                found,
+                expected
+            ),
+            MapError::Metadata { path, row, message } => {
+                write!(f, "{}:{}:1: {}", path.display(), row, message)
+            }
+            MapError::Tiled { path, message } => write!(f, "{}: {}", path.display(), message),
+            MapError::Empty { path } => write!(f, "{}: level is empty", path.display()),
+            MapError::MissingTile { path, tile } => write!(
+                f,
+                "{}: level has no {} tile",
+                path.display(),
+                format!("{:?}", tile).to_lowercase()
+            ),
+        }
+    }
+}
+
+impl Error for MapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MapError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
 impl Map {
-    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let rows: Vec<io::Result<String>> = BufReader::new(file).lines().collect();
+    /// Load a level, reading it as a Tiled map if it is a `.tmx` or `.tmj` file, as ASCII if it is
+    /// a `.txt` file and as CSV otherwise.
+    ///
+    /// Levels without any tiles, or without a start or a goal, cannot be played and are errors.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
+        let path = path.as_ref();
+        let map = if tiled::is_tiled(path) {
+            tiled::load(path)?
+        } else {
+            Self::load_text(path)?
+        };
+        map.validate(path)?;
+        Ok(map)
+    }
+
+    /// Make sure the level at `path` can be played, which takes at least one tile, a start and a
+    /// goal.
+    pub fn validate(&self, path: &Path) -> Result<(), MapError> {
+        // Only the surrounding walls are left of a level without tiles.
+        if self.width <= 2 || self.height <= 2 {
+            return Err(MapError::Empty {
+                path: path.to_path_buf(),
+            });
+        }
+        for tile in [Tile::Start, Tile::Goal] {
+            if !self.tiles().any(|(_, other)| other == tile) {
+                return Err(MapError::MissingTile {
+                    path: path.to_path_buf(),
+                    tile,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a level written as CSV or ASCII text.
+    fn load_text(path: &Path) -> Result<Self, MapError> {
+        let format = Format::from_path(path).unwrap_or(Format::Csv);
+        let io_error = |source| MapError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let file = File::open(path).map_err(io_error)?;
+        let rows = BufReader::new(file)
+            .lines()
+            .collect::<io::Result<Vec<String>>>()
+            .map_err(io_error)?;
+        let columns = rows.first().map_or(0, |row| format.cells(row).len());
+        let mut tiles = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let cells = format.cells(row);
+            let mut row_tiles = Vec::new();
+            for &(column, value) in cells.iter() {
+                let (path, row, text) = (path.to_path_buf(), i + 1, value.to_string());
+                let tile = format.parse(value).map_err(|err| match err {
+                    CellError::NotANumber => MapError::NotANumber {
+                        path,
+                        row,
+                        column,
+                        text,
+                    },
+                    CellError::UnknownTile => MapError::UnknownTile {
+                        path,
+                        row,
+                        column,
+                        text,
+                    },
+                })?;
+                row_tiles.push(tile);
+            }
+            if cells.len() != columns {
+                return Err(MapError::ColumnCount {
+                    path: path.to_path_buf(),
+                    row: i + 1,
+                    text: row.clone(),
+                    expected: columns,
+                    found: cells.len(),
+                });
+            }
+            tiles.push(row_tiles);
+        }
+        Ok(Self::from_rows(&tiles))
+    }
+
+    /// Build a map out of rows of tile characters, as in ASCII levels, surrounded by blocks.
+    #[cfg(test)]
+    pub fn from_ascii(rows: &[&str]) -> Self {
+        let rows: Vec<Vec<Tile>> = rows
+            .iter()
+            .map(|row| {
+                Format::Ascii
+                    .cells(row)
+                    .into_iter()
+                    .map(|(_, cell)| Format::Ascii.parse(cell).unwrap())
+                    .collect()
+            })
+            .collect();
+        Self::from_rows(&rows)
+    }
+
+    /// Build a map out of rows of the same length, surrounded by blocks.
+    pub fn from_rows(rows: &[Vec<Tile>]) -> Self {
         // Add 2 to account for surrounding walls.
-        let size = rows.len() + 2;
-        let mut layout = vec![Tile::Block; size];
+        let columns = rows.first().map_or(0, Vec::len);
+        let (width, height) = (columns + 2, rows.len() + 2);
+        let mut layout = vec![Tile::Block; width];
         for row in rows.iter() {
-            let mut columns = 0;
-            if let Ok(row) = row {
-                layout.push(Tile::Block);
-                for value in row.split(',') {
-                    columns += 1;
-                    layout.push(Tile::try_from(value.parse::<u8>().unwrap()).unwrap());
-                }
-                layout.push(Tile::Block);
-                assert_eq!(
-                    columns,
-                    size - 2,
-                    "Number of columns must be equal to number of rows"
-                );
-            }
+            layout.push(Tile::Block);
+            layout.extend_from_slice(row);
+            layout.push(Tile::Block);
+        }
+        layout.append(&mut vec![Tile::Block; width]);
+        Self {
+            width,
+            height,
+            layout,
         }
-        layout.append(&mut vec![Tile::Block; size]);
-        Ok(Self { size, layout })
+    }
+
+    /// Iterate over every tile along with its coordinates, row by row.
+    pub fn tiles(&self) -> impl Iterator<Item = (Coordinates, Tile)> + '_ {
+        self.layout
+            .iter()
+            .enumerate()
+            .map(move |(i, tile)| (Coordinates::new(i % self.width, i / self.width), *tile))
+    }
+
+    /// The other teleporter of the same color as the one at `coordinates`, if there is one.
+    pub fn partner(&self, coordinates: Coordinates) -> Option<Coordinates> {
+        let tile = self[coordinates.y()][coordinates.x()];
+        if !tile.is_teleporter() {
+            return None;
+        }
+        self.tiles()
+            .find(|&(other, other_tile)| other_tile == tile && other != coordinates)
+            .map(|(other, _)| other)
+    }
+
+    pub fn set(&mut self, coordinates: Coordinates, tile: Tile) {
+        self.layout[coordinates.y() * self.width + coordinates.x()] = tile;
+    }
+
+    /// Replace every tile with the result of `f`.
+    pub fn replace(&mut self, f: impl Fn(Tile) -> Tile) {
+        for tile in self.layout.iter_mut() {
+            *tile = f(*tile);
+        }
+    }
+
+    /// World translation of the center of the tile at the given coordinates.
+    pub fn translation(&self, coordinates: Coordinates) -> Vec3 {
+        let (half_width, half_height) = ((self.width / 2) as isize, (self.height / 2) as isize);
+        Vec3::new(
+            (coordinates.x() as isize - half_width) as f32 * crate::TILE_SIZE,
+            (half_height - coordinates.y() as isize) as f32 * crate::TILE_SIZE,
+            0.0,
+        )
     }
 }
 
@@ -119,7 +634,7 @@ impl Index<usize> for Map {
     type Output = [Tile];
 
     fn index(&self, row: usize) -> &Self::Output {
-        let start = self.size * row;
-        &self.layout[start..start + self.size]
+        let start = self.width * row;
+        &self.layout[start..start + self.width]
     }
 }
@@ -1,12 +1,30 @@
 use crate::{
-    map::{Coordinates, Map, Tile},
-    AppState, MenuState,
+    history::History,
+    map::{Coordinates, Direction},
+    replay::Playback,
+    respawn::RespawnEvent,
+    sim::{GameState, StepOutcome},
+    AppState,
 };
 use bevy::prelude::*;
-use std::cmp::Ordering;
+
+/// Something the player asked to do, either with the keyboard or through a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionEvent {
+    Move(Direction),
+    Undo,
+    Redo,
+    Restart,
+}
 
 #[derive(Debug)]
-pub struct PlayerMovementEvent;
+pub struct PlayerMovementEvent(StepOutcome);
+
+impl PlayerMovementEvent {
+    pub fn outcome(&self) -> StepOutcome {
+        self.0
+    }
+}
 
 #[derive(Component, Debug)]
 pub struct Player;
@@ -15,45 +33,112 @@ impl Player {
     pub const COLOR: Color = Color::rgb(0.53, 0.75, 0.82);
 }
 
-pub fn player_movement(
+/// Number of moves made during the current attempt and the level's par.
+#[derive(Debug, Default)]
+pub struct MoveCount {
+    pub moves: usize,
+    pub par: Option<usize>,
+}
+
+/// Seconds spent in the current attempt.
+#[derive(Debug, Default)]
+pub struct ElapsedTime(pub f32);
+
+pub fn player_input(
     keyboard_input: Res<Input<KeyCode>>,
-    map: Res<Map>,
-    mut player_query: Query<(&mut Transform, &mut Coordinates), With<Player>>,
-    mut events: EventWriter<PlayerMovementEvent>,
+    playback: Option<Res<Playback>>,
+    mut actions: EventWriter<ActionEvent>,
 ) {
-    let (mut transform, mut coordinates) = player_query.single_mut();
-    let initial_coordinates = *coordinates;
+    // The replay is in control while it plays back.
+    if playback.is_some() {
+        return;
+    }
     for code in keyboard_input.get_just_pressed() {
-        let (delta_x, delta_y) = crate::delta_from_code(*code);
-        match delta_x.cmp(&0) {
-            Ordering::Greater => coordinates.move_right(&mut transform.translation, &map),
-            Ordering::Less => coordinates.move_left(&mut transform.translation, &map),
-            _ => {}
+        let action = match code {
+            KeyCode::Z => Some(ActionEvent::Undo),
+            KeyCode::Y => Some(ActionEvent::Redo),
+            KeyCode::R => Some(ActionEvent::Restart),
+            _ => crate::direction_from_code(*code).map(ActionEvent::Move),
+        };
+        if let Some(action) = action {
+            actions.send(action);
         }
-        match delta_y.cmp(&0) {
-            Ordering::Greater => {
-                coordinates.move_down(&mut transform.translation, &map);
+    }
+}
+
+/// Apply every action of the frame in the order it was taken.
+///
+/// Being caught, stepping onto a hazard and restarting start the attempt over straight away, so
+/// any actions after them are played on the reset level.
+pub fn apply_actions(
+    mut actions: EventReader<ActionEvent>,
+    mut game_state: ResMut<GameState>,
+    mut history: ResMut<History>,
+    mut move_count: ResMut<MoveCount>,
+    mut movement_events: EventWriter<PlayerMovementEvent>,
+    mut respawn_events: EventWriter<RespawnEvent>,
+) {
+    for action in actions.iter() {
+        let outcome = match action {
+            ActionEvent::Move(direction) => Some(history.step(&mut game_state, *direction)),
+            ActionEvent::Undo => {
+                history.undo(&mut game_state);
+                None
             }
-            Ordering::Less => {
-                coordinates.move_up(&mut transform.translation, &map);
+            ActionEvent::Redo => history.redo(&mut game_state),
+            ActionEvent::Restart => {
+                history.reset(&mut game_state);
+                respawn_events.send(RespawnEvent::Restart);
+                None
             }
-            _ => {}
+        };
+        if let Some(outcome) = outcome.filter(|&outcome| outcome != StepOutcome::Blocked) {
+            movement_events.send(PlayerMovementEvent(outcome));
         }
-        if *coordinates != initial_coordinates {
-            events.send(PlayerMovementEvent);
+        match outcome {
+            Some(StepOutcome::Caught) => {
+                history.reset(&mut game_state);
+                respawn_events.send(RespawnEvent::Caught);
+            }
+            Some(StepOutcome::Killed) => {
+                history.reset(&mut game_state);
+                respawn_events.send(RespawnEvent::Killed);
+            }
+            // The level is complete, so nothing after the winning move is played.
+            Some(StepOutcome::Won) => break,
+            _ => {}
         }
     }
+    // Undone moves and moves before the level was reset do not count.
+    if move_count.moves != history.moves() {
+        move_count.moves = history.moves();
+    }
+}
+
+pub fn player_sync(
+    game_state: Res<GameState>,
+    mut player_query: Query<(&mut Transform, &mut Coordinates), With<Player>>,
+) {
+    if game_state.is_changed() {
+        let (mut transform, mut coordinates) = player_query.single_mut();
+        *coordinates = game_state.player();
+        transform.translation = game_state.map().translation(*coordinates);
+    }
+}
+
+pub fn tick_elapsed_time(time: Res<Time>, mut elapsed_time: ResMut<ElapsedTime>) {
+    elapsed_time.0 += time.delta_seconds();
 }
 
 pub fn check_completion(
     mut state: ResMut<State<AppState>>,
-    map: Res<Map>,
-    player_query: Query<&Coordinates, (With<Player>, Changed<Coordinates>)>,
+    mut events: EventReader<PlayerMovementEvent>,
 ) {
-    if let Ok(coordinates) = player_query.get_single() {
-        // Return to main menu if the player is on the goal tile.
-        if matches!(map[coordinates.y()][coordinates.x()], Tile::Goal) {
-            state.set(AppState::Menu(MenuState::Main)).unwrap();
+    for event in events.iter() {
+        // Show the level complete screen if the player reached the goal tile.
+        if event.outcome() == StepOutcome::Won {
+            state.set(AppState::LevelComplete).unwrap();
+            break;
         }
     }
 }
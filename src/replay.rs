@@ -0,0 +1,143 @@
+use crate::{map::Direction, player::ActionEvent};
+use bevy::prelude::*;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// Actions played back per second when no speed is given.
+pub const DEFAULT_SPEED: f32 = 4.0;
+
+/// A level name and every action taken while playing it.
+///
+/// Stored as the level name on the first line followed by the actions separated by spaces,
+/// e.g. `up left undo restart`.
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub level: String,
+    pub actions: Vec<ActionEvent>,
+}
+
+impl Replay {
+    pub fn new(level: String) -> Self {
+        Self {
+            level,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        let level = lines.next().unwrap_or_default().trim().to_string();
+        let mut actions = Vec::new();
+        for token in lines.flat_map(str::split_whitespace) {
+            let action = parse_action(token).ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown replay action `{}`", token),
+                )
+            })?;
+            actions.push(action);
+        }
+        Ok(Self { level, actions })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let actions: Vec<String> = self
+            .actions
+            .iter()
+            .map(|action| action_name(*action))
+            .collect();
+        fs::write(path, format!("{}\n{}\n", self.level, actions.join(" ")))
+    }
+}
+
+fn action_name(action: ActionEvent) -> String {
+    match action {
+        ActionEvent::Move(direction) => direction.to_string(),
+        ActionEvent::Undo => "undo".to_string(),
+        ActionEvent::Redo => "redo".to_string(),
+        ActionEvent::Restart => "restart".to_string(),
+    }
+}
+
+fn parse_action(token: &str) -> Option<ActionEvent> {
+    match token {
+        "undo" => Some(ActionEvent::Undo),
+        "redo" => Some(ActionEvent::Redo),
+        "restart" => Some(ActionEvent::Restart),
+        _ => Direction::ALL
+            .into_iter()
+            .find(|direction| direction.to_string() == token)
+            .map(ActionEvent::Move),
+    }
+}
+
+/// Directory that saved replays are written to.
+fn replay_dir() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("move-fast");
+    path.push("replays");
+    Some(path)
+}
+
+/// Actions of a replay that are still waiting to be played back.
+#[derive(Debug)]
+pub struct Playback {
+    actions: VecDeque<ActionEvent>,
+    timer: Timer,
+}
+
+impl Playback {
+    /// Play back a replay at `speed` actions per second.
+    pub fn new(replay: Replay, speed: f32) -> Self {
+        Self {
+            actions: replay.actions.into(),
+            timer: Timer::from_seconds(1.0 / speed, true),
+        }
+    }
+}
+
+pub fn record_actions(mut actions: EventReader<ActionEvent>, mut replay: ResMut<Replay>) {
+    replay.actions.extend(actions.iter().copied());
+}
+
+pub fn play_actions(
+    mut commands: Commands,
+    time: Res<Time>,
+    playback: Option<ResMut<Playback>>,
+    mut actions: EventWriter<ActionEvent>,
+) {
+    if let Some(mut playback) = playback {
+        if playback.timer.tick(time.delta()).just_finished() {
+            match playback.actions.pop_front() {
+                Some(action) => actions.send(action),
+                // Hand control back to the player.
+                None => commands.remove_resource::<Playback>(),
+            }
+        }
+    }
+}
+
+/// Save the replay on F5, while playing or once the level is complete.
+pub fn save_replay(keyboard_input: Res<Input<KeyCode>>, replay: Res<Replay>) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        if let Some(mut path) = replay_dir() {
+            let saved = fs::create_dir_all(&path).and_then(|_| {
+                path.push(format!("{}.replay", replay.level));
+                replay.save(&path)
+            });
+            match saved {
+                Ok(()) => info!("Saved replay to {}", path.display()),
+                Err(err) => warn!("Failed to save replay: {}", err),
+            }
+        }
+    }
+}
+
+pub fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<Playback>();
+}
@@ -1,48 +1,31 @@
-use crate::{enemy::Enemy, map::Coordinates, player::Player};
 use bevy::prelude::*;
 
-#[derive(Debug)]
-pub struct RespawnEvent;
-
-#[derive(Component, Debug)]
-pub struct RespawnPoint {
-    translation: Vec3,
-    coordinates: Coordinates,
+/// Why the level was started over, sent once the reset has happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RespawnEvent {
+    /// The player was caught by an enemy.
+    Caught,
+    /// The player stepped onto a hazard.
+    Killed,
+    /// The player asked to start the level over.
+    Restart,
 }
 
-impl RespawnPoint {
-    pub fn new(translation: Vec3, coordinates: Coordinates) -> Self {
-        Self {
-            translation,
-            coordinates,
-        }
-    }
+/// Number of times the level was reset during the current attempt.
+#[derive(Debug, Default)]
+pub struct RespawnCount {
+    pub deaths: usize,
+    pub restarts: usize,
 }
 
-pub fn respawn_check(
-    mut events: EventWriter<RespawnEvent>,
-    player_query: Query<&Coordinates, With<Player>>,
-    enemy_query: Query<&Coordinates, (With<Enemy>, Without<Player>)>,
-) {
-    let player_coordinates = player_query.single();
-    for enemy_coordinates in enemy_query.iter() {
-        if player_coordinates == enemy_coordinates {
-            events.send(RespawnEvent);
-        }
-    }
-}
-
-type Respawnable<'a> = (&'a mut Transform, &'a mut Coordinates, &'a RespawnPoint);
-
 pub fn respawn_event_listener(
     mut events: EventReader<RespawnEvent>,
-    mut query: Query<Respawnable>,
+    mut respawn_count: ResMut<RespawnCount>,
 ) {
-    for _ in events.iter() {
-        // Reset positions.
-        for (mut transform, mut coordinates, respawn_point) in query.iter_mut() {
-            transform.translation = respawn_point.translation;
-            *coordinates = respawn_point.coordinates;
+    for event in events.iter() {
+        match event {
+            RespawnEvent::Caught | RespawnEvent::Killed => respawn_count.deaths += 1,
+            RespawnEvent::Restart => respawn_count.restarts += 1,
         }
     }
 }
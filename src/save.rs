@@ -0,0 +1,145 @@
+use crate::{
+    level::LevelManager,
+    player::{ElapsedTime, MoveCount, PlayerMovementEvent},
+    replay::Playback,
+    sim::StepOutcome,
+};
+use bevy::prelude::*;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+/// Best results of a completed level.
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    pub moves: usize,
+    pub time: f32,
+}
+
+/// Completed levels and their best results, keyed by level name.
+///
+/// Stored as CSV lines of `name,moves,time` in the user's data directory. Names may contain commas
+/// but not line breaks.
+#[derive(Debug, Default)]
+pub struct Progress {
+    records: HashMap<String, Record>,
+}
+
+impl Progress {
+    fn path() -> Option<PathBuf> {
+        let mut path = dirs::data_dir()?;
+        path.push("move-fast");
+        path.push("progress.csv");
+        Some(path)
+    }
+
+    /// Load saved progress, starting afresh if nothing has been saved yet or the save cannot be read.
+    ///
+    /// This runs before logging is set up, so warnings go straight to stderr.
+    pub fn load() -> Self {
+        let mut progress = Progress::default();
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return progress,
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return progress,
+            Err(err) => {
+                eprintln!(
+                    "Ignoring unreadable progress in {}: {}",
+                    path.display(),
+                    err
+                );
+                return progress;
+            }
+        };
+        for line in contents.lines() {
+            // The numbers are split off the end, so that the name keeps any commas in it.
+            let values: Vec<&str> = line.rsplitn(3, ',').collect();
+            let record = match values.as_slice() {
+                [time, moves, name] => match (moves.parse(), time.parse()) {
+                    (Ok(moves), Ok(time)) => Some((name, Record { moves, time })),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match record {
+                Some((name, record)) => {
+                    progress.records.insert(name.to_string(), record);
+                }
+                None => eprintln!("Ignoring malformed progress line `{}`", line),
+            }
+        }
+        progress
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut names: Vec<&String> = self.records.keys().collect();
+        names.sort();
+        let contents: String = names
+            .into_iter()
+            .map(|name| {
+                let record = self.records[name];
+                format!("{},{},{}\n", name, record.moves, record.time)
+            })
+            .collect();
+        fs::write(path, contents)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Record> {
+        self.records.get(name)
+    }
+
+    /// Mark a level as completed, keeping the best moves and time seen so far.
+    pub fn record(&mut self, name: &str, moves: usize, time: f32) {
+        let record = self
+            .records
+            .entry(name.to_string())
+            .or_insert(Record { moves, time });
+        record.moves = record.moves.min(moves);
+        record.time = record.time.min(time);
+    }
+}
+
+/// Record the level as completed once the player reaches the goal.
+pub fn record_completion(
+    mut progress: ResMut<Progress>,
+    level_manager: Res<LevelManager>,
+    playback: Option<Res<Playback>>,
+    move_count: Res<MoveCount>,
+    elapsed_time: Res<ElapsedTime>,
+    mut events: EventReader<PlayerMovementEvent>,
+) {
+    if !events
+        .iter()
+        .any(|event| event.outcome() == StepOutcome::Won)
+    {
+        return;
+    }
+    // Test plays from the editor and replays of someone else's run are not progress.
+    if level_manager.is_testing() || playback.is_some() {
+        return;
+    }
+    if let Some(name) = level_manager.current_name() {
+        // A line break would split the record across lines of the progress file.
+        if name.contains(&['\n', '\r'][..]) {
+            warn!("Not saving progress of level `{}`", name.escape_debug());
+            return;
+        }
+        progress.record(name, move_count.moves, elapsed_time.0);
+        if let Err(err) = progress.save() {
+            warn!("Failed to save progress: {}", err);
+        }
+    }
+}
@@ -0,0 +1,654 @@
+use crate::{
+    enemy::EnemyKind,
+    map::{Coordinates, Direction, Map, Tile},
+};
+use std::{cmp::Ordering, collections::VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The player moved and the enemies took their turn.
+    Moved,
+    /// The player could not move; nothing happened.
+    Blocked,
+    /// The player and an enemy ended up on the same tile.
+    ///
+    /// Walking into an enemy is caught before the enemies move, so the player and an enemy can
+    /// never swap places by crossing each other.
+    Caught,
+    /// The player stepped onto a hazard.
+    Killed,
+    /// The player reached the goal.
+    Won,
+}
+
+/// Position and behaviour state of a single enemy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnemyState {
+    pub kind: EnemyKind,
+    pub coordinates: Coordinates,
+    /// Direction a patroller is walking in.
+    pub heading: Direction,
+    /// Whether a sleeper has spotted the player.
+    pub awake: bool,
+    /// Whether the enemy is still in play, as opposed to having stepped onto a hazard.
+    pub alive: bool,
+}
+
+impl EnemyState {
+    fn new(kind: EnemyKind, coordinates: Coordinates) -> Self {
+        let heading = match kind {
+            EnemyKind::Patroller(heading) => heading,
+            _ => Direction::Up,
+        };
+        Self {
+            kind,
+            coordinates,
+            heading,
+            awake: false,
+            alive: true,
+        }
+    }
+}
+
+/// Level-specific tweaks to how enemies interact with tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    /// Whether teleporters move enemies as well as the player.
+    pub enemies_teleport: bool,
+    /// Whether enemies that step onto a hazard are removed, rather than walking over it unharmed.
+    pub hazards_remove_enemies: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            enemies_teleport: true,
+            hazards_remove_enemies: true,
+        }
+    }
+}
+
+/// Positions of everything that can change during an attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    pub player: Coordinates,
+    pub enemies: Vec<EnemyState>,
+    pub crates: Vec<Coordinates>,
+    /// The level's tiles, with collected keys, opened doors, toggled gates and crumbled floors.
+    pub map: Map,
+}
+
+/// The turn rules of a level, independent of any rendering or ECS state.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    rules: Rules,
+    initial: Snapshot,
+    current: Snapshot,
+}
+
+impl GameState {
+    pub fn new(map: Map, rules: Rules) -> Self {
+        let mut player = Coordinates::new(map.width / 2, map.height / 2);
+        let mut enemies = Vec::new();
+        let mut crates = Vec::new();
+        for (coordinates, tile) in map.tiles() {
+            if let Tile::Start = tile {
+                player = coordinates;
+            } else if let Tile::Crate = tile {
+                crates.push(coordinates);
+            } else if let Some(kind) = tile.enemy_kind() {
+                enemies.push(EnemyState::new(kind, coordinates));
+            }
+        }
+        let initial = Snapshot {
+            player,
+            enemies,
+            crates,
+            map,
+        };
+        Self {
+            rules,
+            current: initial.clone(),
+            initial,
+        }
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.current.map
+    }
+
+    pub fn player(&self) -> Coordinates {
+        self.current.player
+    }
+
+    pub fn enemies(&self) -> &[EnemyState] {
+        &self.current.enemies
+    }
+
+    pub fn crates(&self) -> &[Coordinates] {
+        &self.current.crates
+    }
+
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.current
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.current = snapshot;
+    }
+
+    /// Number of gems collected so far and the number of gems in the level.
+    pub fn gems(&self) -> (usize, usize) {
+        let count = |map: &Map| map.tiles().filter(|&(_, tile)| tile == Tile::Gem).count();
+        let total = count(&self.initial.map);
+        (total - count(&self.current.map), total)
+    }
+
+    /// Put the player, every enemy, every crate and every tile back the way the level started them.
+    pub fn reset(&mut self) {
+        self.current = self.initial.clone();
+    }
+
+    /// Play a single turn: the player moves, then every enemy takes its turn.
+    pub fn step(&mut self, direction: Direction) -> StepOutcome {
+        let (state, rules) = (&mut self.current, self.rules);
+        let origin = state.player;
+        if !state.player.move_in(direction, &state.map) {
+            return StepOutcome::Blocked;
+        }
+        // Push a crate out of the way, unless there is something behind it.
+        if let Some(i) = state.crates.iter().position(|&c| c == state.player) {
+            let mut pushed = state.player;
+            if !pushed.move_in(direction, &state.map) || state.is_occupied(pushed) {
+                state.player = origin;
+                return StepOutcome::Blocked;
+            }
+            state.crates[i] = pushed;
+        }
+        // Crumbling floors give way once the player steps off them.
+        if let Tile::CrumblingFloor = state.map[origin.y()][origin.x()] {
+            state.map.set(origin, Tile::Block);
+        }
+        // The player walked into an enemy, which must not get the chance to step away.
+        if state.is_caught() {
+            return StepOutcome::Caught;
+        }
+        let partner = state.map.partner(state.player);
+        if let Some(partner) = partner.filter(|partner| !state.crates.contains(partner)) {
+            state.player = partner;
+            if state.is_caught() {
+                return StepOutcome::Caught;
+            }
+        }
+        let tile = state.map[state.player.y()][state.player.x()];
+        match tile {
+            // Goals only open once every gem has been collected.
+            Tile::Goal if !state.map.tiles().any(|(_, tile)| tile == Tile::Gem) => {
+                return StepOutcome::Won;
+            }
+            Tile::Gem => state.map.set(state.player, Tile::Empty),
+            Tile::Spikes | Tile::Pit => return StepOutcome::Killed,
+            Tile::Switch => state.map.replace(|tile| match tile {
+                Tile::Gate => Tile::OpenGate,
+                Tile::OpenGate => Tile::Gate,
+                tile => tile,
+            }),
+            _ => {
+                // A key is collected along with every other key of its color, opening their doors.
+                if let Some(door) = tile.door() {
+                    state.map.replace(|other| {
+                        if other == tile || other == door {
+                            Tile::Empty
+                        } else {
+                            other
+                        }
+                    });
+                }
+            }
+        }
+        // Enemies cannot tell crates apart from blocks.
+        let mut obstacles = state.map.clone();
+        for &coordinates in state.crates.iter() {
+            obstacles.set(coordinates, Tile::Block);
+        }
+        // Enemies closest to the player move first, ties are broken by spawn order.
+        let mut order: Vec<usize> = (0..state.enemies.len()).collect();
+        order.sort_by_key(|&i| (distance(state.enemies[i].coordinates, state.player), i));
+        for i in order {
+            let steps = if let EnemyKind::Fast = state.enemies[i].kind {
+                2
+            } else {
+                1
+            };
+            for _ in 0..steps {
+                let enemy = &state.enemies[i];
+                if !enemy.alive || enemy.coordinates == state.player {
+                    break;
+                }
+                state.enemy_step(i, direction, &obstacles, rules);
+            }
+        }
+        if state.is_caught() {
+            StepOutcome::Caught
+        } else {
+            StepOutcome::Moved
+        }
+    }
+}
+
+impl Snapshot {
+    fn is_caught(&self) -> bool {
+        self.enemies
+            .iter()
+            .any(|enemy| enemy.alive && enemy.coordinates == self.player)
+    }
+
+    /// Whether an enemy or a crate stands on the tile.
+    fn is_occupied(&self, coordinates: Coordinates) -> bool {
+        self.crates.contains(&coordinates)
+            || self
+                .enemies
+                .iter()
+                .any(|enemy| enemy.alive && enemy.coordinates == coordinates)
+    }
+
+    /// Move the enemy at `index` by a single tile according to its kind.
+    ///
+    /// `map` is the level as the enemies see it, with every crate in place of a block.
+    fn enemy_step(&mut self, index: usize, player_direction: Direction, map: &Map, rules: Rules) {
+        let mut enemy = self.enemies[index];
+        let (origin, player) = (enemy.coordinates, self.player);
+        let occupied = |coordinates| {
+            self.enemies
+                .iter()
+                .any(|other| other.alive && other.coordinates == coordinates)
+        };
+        // Enemies cannot move onto a tile occupied by another enemy.
+        let advance = |direction: Direction| {
+            let mut coordinates = origin;
+            if coordinates.move_in(direction, map) && !occupied(coordinates) {
+                Some(coordinates)
+            } else {
+                None
+            }
+        };
+        let next = match enemy.kind {
+            EnemyKind::Chaser | EnemyKind::Fast => {
+                chase_direction(origin, player, false).and_then(advance)
+            }
+            EnemyKind::VerticalChaser => chase_direction(origin, player, true).and_then(advance),
+            EnemyKind::Hunter => hunt_direction(origin, player, map, rules).and_then(advance),
+            EnemyKind::Mirror => advance(player_direction.opposite()),
+            EnemyKind::Patroller(_) => advance(enemy.heading).or_else(|| {
+                // Turn around at the end of the path.
+                enemy.heading = enemy.heading.opposite();
+                advance(enemy.heading)
+            }),
+            EnemyKind::Sleeper => {
+                enemy.awake = enemy.awake || in_line_of_sight(origin, player, map);
+                if enemy.awake {
+                    chase_direction(origin, player, false).and_then(advance)
+                } else {
+                    None
+                }
+            }
+        };
+        if let Some(coordinates) = next {
+            enemy.coordinates = coordinates;
+            // Teleporters move enemies too, unless they caught the player on the teleporter or
+            // another enemy stands on the partner.
+            if rules.enemies_teleport && coordinates != player {
+                if let Some(partner) = map.partner(coordinates).filter(|&p| !occupied(p)) {
+                    enemy.coordinates = partner;
+                }
+            }
+            // Enemies that step onto a hazard are gone for the rest of the attempt.
+            let tile = map[enemy.coordinates.y()][enemy.coordinates.x()];
+            enemy.alive = !(rules.hazards_remove_enemies && tile.is_hazard());
+        }
+        self.enemies[index] = enemy;
+    }
+}
+
+/// Manhattan distance between two tiles.
+fn distance(a: Coordinates, b: Coordinates) -> usize {
+    (a.x() as isize - b.x() as isize).unsigned_abs()
+        + (a.y() as isize - b.y() as isize).unsigned_abs()
+}
+
+/// Direction that brings an enemy closer to the player along the axis with the larger distance.
+///
+/// Ties between the axes are broken horizontally, or vertically if `vertical_first` is set.
+fn chase_direction(
+    enemy: Coordinates,
+    player: Coordinates,
+    vertical_first: bool,
+) -> Option<Direction> {
+    let (delta_x, delta_y) = (
+        enemy.x() as isize - player.x() as isize,
+        enemy.y() as isize - player.y() as isize,
+    );
+    let horizontal = if vertical_first {
+        delta_x.abs() > delta_y.abs()
+    } else {
+        delta_x.abs() >= delta_y.abs()
+    };
+    if horizontal {
+        match delta_x.cmp(&0) {
+            Ordering::Greater => Some(Direction::Left),
+            Ordering::Less => Some(Direction::Right),
+            Ordering::Equal => None,
+        }
+    } else {
+        match delta_y.cmp(&0) {
+            Ordering::Greater => Some(Direction::Up),
+            Ordering::Less => Some(Direction::Down),
+            Ordering::Equal => None,
+        }
+    }
+}
+
+/// First step of a shortest path around solid tiles and deadly hazards and through teleporters
+/// from an enemy to the player.
+///
+/// Ties between equally short paths are broken in the order of [`Direction::ALL`].
+fn hunt_direction(
+    enemy: Coordinates,
+    player: Coordinates,
+    map: &Map,
+    rules: Rules,
+) -> Option<Direction> {
+    let index = |coordinates: Coordinates| coordinates.y() * map.width + coordinates.x();
+    let mut visited = vec![false; map.width * map.height];
+    visited[index(enemy)] = true;
+    // Teleporters are followed, except onto the player's own tile.
+    let neighbour = |coordinates: Coordinates, direction| {
+        let mut next = coordinates;
+        let deadly =
+            |next: Coordinates| rules.hazards_remove_enemies && map[next.y()][next.x()].is_hazard();
+        if !next.move_in(direction, map) || deadly(next) {
+            None
+        } else if next == player || !rules.enemies_teleport {
+            Some(next)
+        } else {
+            Some(map.partner(next).unwrap_or(next))
+        }
+    };
+    // Every tile is queued along with the first step taken to reach it. Since the first steps are
+    // queued in order, the first path to reach the player starts with the preferred direction.
+    let mut queue = VecDeque::new();
+    for direction in Direction::ALL {
+        if let Some(next) = neighbour(enemy, direction) {
+            if !visited[index(next)] {
+                visited[index(next)] = true;
+                queue.push_back((next, direction));
+            }
+        }
+    }
+    while let Some((coordinates, first)) = queue.pop_front() {
+        if coordinates == player {
+            return Some(first);
+        }
+        for direction in Direction::ALL {
+            if let Some(next) = neighbour(coordinates, direction) {
+                if !visited[index(next)] {
+                    visited[index(next)] = true;
+                    queue.push_back((next, first));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Whether two tiles share a row or column with no solid tile between them.
+fn in_line_of_sight(a: Coordinates, b: Coordinates, map: &Map) -> bool {
+    if a.x() == b.x() {
+        let (top, bottom) = (a.y().min(b.y()), a.y().max(b.y()));
+        (top..=bottom).all(|y| !map[y][a.x()].is_solid())
+    } else if a.y() == b.y() {
+        let (left, right) = (a.x().min(b.x()), a.x().max(b.x()));
+        map[a.y()][left..=right].iter().all(|tile| !tile.is_solid())
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a level from rows of tile characters, surrounded by walls.
+    fn level(rows: &[&str]) -> GameState {
+        GameState::new(Map::from_ascii(rows), Rules::default())
+    }
+
+    /// Coordinates of the tile at `column` and `row` of the rows given to `level`.
+    fn at(column: usize, row: usize) -> Coordinates {
+        Coordinates::new(column + 1, row + 1)
+    }
+
+    /// Tile of the current map at the given coordinates.
+    fn tile(game_state: &GameState, coordinates: Coordinates) -> Tile {
+        game_state.map()[coordinates.y()][coordinates.x()]
+    }
+
+    #[test]
+    fn closest_enemy_moves_first() {
+        // The farther enemy spawns first, but only follows once the closer one has made room.
+        let mut game_state = level(&["EE...S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+        assert_eq!(game_state.enemies()[1].coordinates, at(2, 0));
+    }
+
+    #[test]
+    fn walking_into_enemy_is_caught_before_it_moves() {
+        // The patroller would walk away to the right if it got its turn.
+        let mut game_state = level(&["SH.."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Caught);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn player_and_enemy_cannot_swap_places() {
+        // The chaser would step onto the start tile if the player got past it.
+        let mut game_state = level(&["SE."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Caught);
+        assert_eq!(game_state.player(), at(1, 0));
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn hunter_breaks_ties_in_direction_order() {
+        // Going over and under the block are equally short, and up comes first.
+        let mut game_state = level(&["..S", "X#.", "..."]);
+        assert_eq!(game_state.step(Direction::Down), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 0));
+    }
+
+    #[test]
+    fn enemy_is_not_teleported_onto_occupied_partner() {
+        let rows = [".1E.", "S...", "...1"];
+        let mut game_state = level(&rows);
+        assert_eq!(game_state.step(Direction::Up), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(3, 2));
+
+        let mut game_state = level(&rows);
+        let blocker = EnemyState::new(EnemyKind::Chaser, at(3, 2));
+        game_state.current.enemies.push(blocker);
+        assert_eq!(game_state.step(Direction::Up), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn player_is_not_teleported_onto_crate() {
+        let mut game_state = level(&["S1.", "..1"]);
+        game_state.current.crates.push(at(2, 1));
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.player(), at(1, 0));
+    }
+
+    #[test]
+    fn crate_is_pushed_unless_something_is_behind_it() {
+        let mut game_state = level(&["S$."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.player(), at(1, 0));
+        assert_eq!(game_state.crates(), [at(2, 0)]);
+        // The crate is now against the wall.
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Blocked);
+        assert_eq!(game_state.player(), at(1, 0));
+        assert_eq!(game_state.crates(), [at(2, 0)]);
+
+        let mut game_state = level(&["S$$."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Blocked);
+        assert_eq!(game_state.crates(), [at(1, 0), at(2, 0)]);
+    }
+
+    #[test]
+    fn goal_opens_once_every_gem_is_collected() {
+        let mut game_state = level(&["SG*"]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.gems(), (1, 1));
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Won);
+    }
+
+    #[test]
+    fn vertical_chaser_breaks_ties_vertically() {
+        // The player ends up two columns and two rows away from the enemy.
+        let mut game_state = level(&["V...", "....", "...S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 1));
+
+        let mut game_state = level(&["E...", "....", "...S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn mirror_moves_opposite_to_the_player() {
+        let mut game_state = level(&[".M.", "...", "S.."]);
+        assert_eq!(game_state.step(Direction::Up), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 1));
+    }
+
+    #[test]
+    fn patroller_turns_around_when_blocked() {
+        let mut game_state = level(&["..H", "...", "S.."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+        assert_eq!(game_state.enemies()[0].heading, Direction::Left);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 0));
+    }
+
+    #[test]
+    fn sleeper_wakes_once_it_sees_the_player() {
+        let mut game_state = level(&["Z...", "....", ".S.."]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert!(game_state.enemies()[0].awake);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 1));
+
+        // Crates block the line of sight like blocks do.
+        let mut game_state = level(&["Z...", "$...", ".S.."]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert!(!game_state.enemies()[0].awake);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 0));
+    }
+
+    #[test]
+    fn fast_enemy_takes_two_steps_until_it_reaches_the_player() {
+        let mut game_state = level(&["F....S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(2, 0));
+
+        let mut game_state = level(&["F.S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Caught);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn key_opens_every_door_of_its_color() {
+        let mut game_state = level(&["SAa"]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Blocked);
+
+        let mut game_state = level(&["SaA.", "a..A", "...B"]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        for coordinates in [at(1, 0), at(2, 0), at(0, 1), at(3, 1)] {
+            assert_eq!(tile(&game_state, coordinates), Tile::Empty);
+        }
+        assert_eq!(tile(&game_state, at(3, 2)), Tile::GreenDoor);
+    }
+
+    #[test]
+    fn switch_toggles_every_gate() {
+        let mut game_state = level(&["So=_"]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(tile(&game_state, at(2, 0)), Tile::OpenGate);
+        assert_eq!(tile(&game_state, at(3, 0)), Tile::Gate);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(tile(&game_state, at(2, 0)), Tile::Gate);
+        assert_eq!(tile(&game_state, at(3, 0)), Tile::OpenGate);
+    }
+
+    #[test]
+    fn one_way_tile_can_only_be_entered_in_its_direction() {
+        let mut game_state = level(&["S>."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.player(), at(1, 0));
+
+        let mut game_state = level(&["S<."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Blocked);
+        assert_eq!(game_state.player(), at(0, 0));
+    }
+
+    #[test]
+    fn crumbling_floor_becomes_a_block_once_left() {
+        let mut game_state = level(&["S~."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(tile(&game_state, at(1, 0)), Tile::CrumblingFloor);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(tile(&game_state, at(1, 0)), Tile::Block);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Blocked);
+    }
+
+    #[test]
+    fn hazards_kill_the_player() {
+        assert_eq!(level(&["Sx"]).step(Direction::Right), StepOutcome::Killed);
+        assert_eq!(level(&["SO"]).step(Direction::Right), StepOutcome::Killed);
+    }
+
+    #[test]
+    fn hazards_remove_enemies_only_when_the_rule_is_set() {
+        let mut game_state = level(&["Ex.S"]);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert!(!game_state.enemies()[0].alive);
+
+        let rules = Rules {
+            hazards_remove_enemies: false,
+            ..Rules::default()
+        };
+        let mut game_state = GameState::new(Map::from_ascii(&["Ex.S"]), rules);
+        assert_eq!(game_state.step(Direction::Left), StepOutcome::Moved);
+        assert!(game_state.enemies()[0].alive);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+    }
+
+    #[test]
+    fn hunter_avoids_deadly_hazards() {
+        // Both ways around are equally short, and down would come first.
+        let mut game_state = level(&["X.", "x.", "S."]);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(1, 0));
+
+        let rules = Rules {
+            hazards_remove_enemies: false,
+            ..Rules::default()
+        };
+        let mut game_state = GameState::new(Map::from_ascii(&["X.", "x.", "S."]), rules);
+        assert_eq!(game_state.step(Direction::Right), StepOutcome::Moved);
+        assert_eq!(game_state.enemies()[0].coordinates, at(0, 1));
+    }
+}
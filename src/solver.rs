@@ -0,0 +1,167 @@
+use crate::{
+    map::{Coordinates, Direction, Tile},
+    sim::{EnemyState, GameState, Snapshot, StepOutcome},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Number of states searched before giving up, which keeps large open levels from running out of
+/// memory.
+pub const MAX_STATES: usize = 200_000;
+
+/// Why a search found no solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsolved {
+    /// Every reachable state was searched, so the level cannot be won.
+    Unwinnable,
+    /// The search reached its limit of states before it could finish.
+    GaveUp,
+    /// The search was stopped from another thread before it could finish.
+    Cancelled,
+}
+
+/// A snapshot that only stores the tiles that differ from the starting map, since most tiles never
+/// change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    player: Coordinates,
+    enemies: Vec<EnemyState>,
+    crates: Vec<Coordinates>,
+    /// Index into the map's tiles, row by row, and value of every tile that changed.
+    changed_tiles: Vec<(u32, Tile)>,
+}
+
+impl State {
+    fn new(snapshot: &Snapshot, start: &Snapshot) -> Self {
+        let changed_tiles = snapshot
+            .map
+            .tiles()
+            .zip(start.map.tiles())
+            .enumerate()
+            .filter(|(_, ((_, tile), (_, start_tile)))| tile != start_tile)
+            .map(|(i, ((_, tile), _))| (i as u32, tile))
+            .collect();
+        Self {
+            player: snapshot.player,
+            enemies: snapshot.enemies.clone(),
+            crates: snapshot.crates.clone(),
+            changed_tiles,
+        }
+    }
+
+    fn snapshot(&self, start: &Snapshot) -> Snapshot {
+        let mut map = start.map.clone();
+        for &(i, tile) in self.changed_tiles.iter() {
+            let i = i as usize;
+            map.set(Coordinates::new(i % map.width, i / map.width), tile);
+        }
+        Snapshot {
+            player: self.player,
+            enemies: self.enemies.clone(),
+            crates: self.crates.clone(),
+            map,
+        }
+    }
+}
+
+/// Find the shortest sequence of moves that wins the level from its current state.
+///
+/// Performs a breadth-first search over every reachable arrangement of the player and enemies,
+/// giving up once it has seen `max_states` of them or once `cancel` is set.
+pub fn solve(
+    game_state: &GameState,
+    max_states: usize,
+    cancel: &AtomicBool,
+) -> Result<Vec<Direction>, Unsolved> {
+    let mut state = game_state.clone();
+    let start = state.snapshot().clone();
+    // Maps each visited state to its index in `parents`, which holds the index of the state and
+    // the move that first reached it.
+    let mut visited = HashMap::from([(State::new(&start, &start), 0)]);
+    let mut parents: Vec<Option<(usize, Direction)>> = vec![None];
+    let mut queue = VecDeque::from([(0, State::new(&start, &start))]);
+    while let Some((index, current)) = queue.pop_front() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(Unsolved::Cancelled);
+        }
+        let snapshot = current.snapshot(&start);
+        for direction in Direction::ALL {
+            state.restore(snapshot.clone());
+            match state.step(direction) {
+                StepOutcome::Won => {
+                    let mut moves = vec![direction];
+                    let mut current = index;
+                    while let Some((parent, direction)) = parents[current] {
+                        moves.push(direction);
+                        current = parent;
+                    }
+                    moves.reverse();
+                    return Ok(moves);
+                }
+                StepOutcome::Moved => {
+                    let next = State::new(state.snapshot(), &start);
+                    if !visited.contains_key(&next) {
+                        if parents.len() >= max_states {
+                            return Err(Unsolved::GaveUp);
+                        }
+                        visited.insert(next.clone(), parents.len());
+                        queue.push_back((parents.len(), next));
+                        parents.push(Some((index, direction)));
+                    }
+                }
+                StepOutcome::Blocked | StepOutcome::Caught | StepOutcome::Killed => {}
+            }
+        }
+    }
+    Err(Unsolved::Unwinnable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map::Map, sim::Rules};
+
+    #[test]
+    fn finds_shortest_solution() {
+        let game_state = GameState::new(Map::from_ascii(&["S#G", "..."]), Rules::default());
+        assert_eq!(
+            solve(&game_state, MAX_STATES, &AtomicBool::new(false)),
+            Ok(vec![
+                Direction::Down,
+                Direction::Right,
+                Direction::Right,
+                Direction::Up
+            ])
+        );
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_solution() {
+        let game_state = GameState::new(Map::from_ascii(&["S#G"]), Rules::default());
+        assert_eq!(
+            solve(&game_state, MAX_STATES, &AtomicBool::new(false)),
+            Err(Unsolved::Unwinnable)
+        );
+    }
+
+    #[test]
+    fn search_gives_up_after_too_many_states() {
+        let rows = ["S....", ".....", "....#", "...#G"];
+        let game_state = GameState::new(Map::from_ascii(&rows), Rules::default());
+        assert_eq!(
+            solve(&game_state, 10, &AtomicBool::new(false)),
+            Err(Unsolved::GaveUp)
+        );
+    }
+
+    #[test]
+    fn cancelled_search_stops() {
+        let game_state = GameState::new(Map::from_ascii(&["S#G", "..."]), Rules::default());
+        assert_eq!(
+            solve(&game_state, MAX_STATES, &AtomicBool::new(true)),
+            Err(Unsolved::Cancelled)
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::map::{Map, MapError, Tile};
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use std::{convert::TryFrom, ffi::OsStr, fs, path::Path};
+
+/// Bits of a global tile ID that Tiled uses to flip and rotate tiles.
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+/// The parts of a Tiled map that make up a level.
+#[derive(Debug, Default)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f64,
+    tile_height: f64,
+    /// Global ID of the first tile of the first tileset.
+    first_gid: u32,
+    /// Global tile IDs of every tile layer, row by row.
+    layers: Vec<Vec<u32>>,
+    objects: Vec<Object>,
+}
+
+#[derive(Debug)]
+struct Object {
+    /// Type of the object, naming the tile it stands for.
+    kind: String,
+    gid: Option<u32>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Whether the level at `path` is a Tiled map, in either its XML or JSON format.
+pub fn is_tiled<P: AsRef<Path>>(path: P) -> bool {
+    matches!(
+        path.as_ref().extension().and_then(OsStr::to_str),
+        Some("tmx") | Some("tmj")
+    )
+}
+
+/// Load a Tiled map as a level.
+///
+/// Tile layers are stacked in order, and the ID of a tile within the first tileset is its tile
+/// value. Objects are placed on the tile under their center, as the tile their own tile ID stands
+/// for or, if they are not tile objects, the tile named by their type, such as `start` or
+/// `hunter_enemy`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Map, MapError> {
+    let path = path.as_ref();
+    let error = |message| MapError::Tiled {
+        path: path.to_path_buf(),
+        message,
+    };
+    let contents = fs::read_to_string(path).map_err(|source| MapError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let tiled = if path.extension() == Some(OsStr::new("tmx")) {
+        parse_tmx(&contents)
+    } else {
+        parse_tmj(&contents)
+    }
+    .map_err(error)?;
+
+    let tile = |gid: u32| {
+        let id = (gid & !FLIP_FLAGS).saturating_sub(tiled.first_gid);
+        u8::try_from(id)
+            .ok()
+            .and_then(|id| Tile::try_from(id).ok())
+            .ok_or_else(|| error(format!("tile ID {} is not a tile value", id)))
+    };
+    let mut rows = vec![vec![Tile::Empty; tiled.width]; tiled.height];
+    for layer in tiled.layers.iter() {
+        if layer.len() != tiled.width * tiled.height {
+            return Err(error(format!(
+                "tile layer has {} tiles but the map is {}x{}",
+                layer.len(),
+                tiled.width,
+                tiled.height
+            )));
+        }
+        for (i, &gid) in layer.iter().enumerate() {
+            // Empty cells leave the tiles of the layers below visible.
+            if gid != 0 {
+                rows[i / tiled.width][i % tiled.width] = tile(gid)?;
+            }
+        }
+    }
+    for object in tiled.objects.iter() {
+        let placed = match object.gid {
+            Some(gid) => tile(gid)?,
+            None => tile_named(&object.kind)
+                .ok_or_else(|| error(format!("unknown object type `{}`", object.kind)))?,
+        };
+        // Tile objects are positioned by their bottom left corner, other objects by their top left.
+        let top = match object.gid {
+            Some(_) => object.y - object.height,
+            None => object.y,
+        };
+        let column = ((object.x + object.width / 2.0) / tiled.tile_width).floor();
+        let row = ((top + object.height / 2.0) / tiled.tile_height).floor();
+        if column < 0.0
+            || row < 0.0
+            || column as usize >= tiled.width
+            || row as usize >= tiled.height
+        {
+            return Err(error(format!(
+                "`{}` object is outside the map",
+                object.kind
+            )));
+        }
+        rows[row as usize][column as usize] = placed;
+    }
+    Ok(Map::from_rows(&rows))
+}
+
+/// Tile with the given name, ignoring case, spaces and underscores.
+fn tile_named(name: &str) -> Option<Tile> {
+    let name: String = name
+        .chars()
+        .filter(|character| character.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    (0..Tile::COUNT)
+        .filter_map(|value| Tile::try_from(value).ok())
+        .find(|tile| format!("{:?}", tile).to_lowercase() == name)
+}
+
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, String> {
+    node.attribute(name)
+        .ok_or_else(|| format!("<{}> has no `{}` attribute", node.tag_name().name(), name))
+}
+
+fn number(node: Node, name: &str) -> Result<f64, String> {
+    let value = attribute(node, name)?;
+    value
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid `{}`", value, name))
+}
+
+fn parse_tmx(contents: &str) -> Result<TiledMap, String> {
+    let document = Document::parse(contents).map_err(|err| err.to_string())?;
+    let map = document.root_element();
+    let mut tiled = TiledMap {
+        width: number(map, "width")? as usize,
+        height: number(map, "height")? as usize,
+        tile_width: number(map, "tilewidth")?,
+        tile_height: number(map, "tileheight")?,
+        first_gid: 1,
+        ..TiledMap::default()
+    };
+    if let Some(tileset) = map.children().find(|node| node.has_tag_name("tileset")) {
+        tiled.first_gid = number(tileset, "firstgid")? as u32;
+    }
+    // Only layers of the map and of its groups make up the level. Tilesets may hold object groups
+    // too, for the collision shapes of their tiles, so the document is not searched as a whole.
+    let mut nodes: Vec<Node> = map.children().filter(Node::is_element).collect();
+    nodes.reverse();
+    while let Some(node) = nodes.pop() {
+        match node.tag_name().name() {
+            "layer" => {
+                let data = node
+                    .children()
+                    .find(|node| node.has_tag_name("data"))
+                    .ok_or("tile layer has no data")?;
+                if data.attribute("encoding") != Some("csv") {
+                    return Err("only CSV encoded tile layers are supported".to_string());
+                }
+                let gids = data
+                    .text()
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|gid| {
+                        let gid = gid.trim();
+                        gid.parse()
+                            .map_err(|_| format!("`{}` is not a tile ID", gid))
+                    })
+                    .collect::<Result<_, _>>()?;
+                tiled.layers.push(gids);
+            }
+            "objectgroup" => {
+                for object in node.children().filter(|node| node.has_tag_name("object")) {
+                    // Tiled 1.9 renamed the type of an object to its class, and objects without
+                    // either may still be named after a tile.
+                    let kind = object
+                        .attribute("type")
+                        .or_else(|| object.attribute("class"))
+                        .filter(|kind| !kind.is_empty())
+                        .or_else(|| object.attribute("name"));
+                    tiled.objects.push(Object {
+                        kind: kind.unwrap_or_default().to_string(),
+                        gid: object.attribute("gid").and_then(|gid| gid.parse().ok()),
+                        x: number(object, "x")?,
+                        y: number(object, "y")?,
+                        width: number(object, "width").unwrap_or_default(),
+                        height: number(object, "height").unwrap_or_default(),
+                    });
+                }
+            }
+            "group" => {
+                let start = nodes.len();
+                nodes.extend(node.children().filter(Node::is_element));
+                nodes[start..].reverse();
+            }
+            _ => {}
+        }
+    }
+    Ok(tiled)
+}
+
+fn parse_tmj(contents: &str) -> Result<TiledMap, String> {
+    let map: Value = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    let number = |value: &Value, key| {
+        value[key]
+            .as_f64()
+            .ok_or_else(|| format!("`{}` is missing or not a number", key))
+    };
+    let mut tiled = TiledMap {
+        width: number(&map, "width")? as usize,
+        height: number(&map, "height")? as usize,
+        tile_width: number(&map, "tilewidth")?,
+        tile_height: number(&map, "tileheight")?,
+        first_gid: map["tilesets"][0]["firstgid"].as_u64().unwrap_or(1) as u32,
+        ..TiledMap::default()
+    };
+    // Group layers hold more layers, so walk them depth first.
+    let mut layers: Vec<&Value> = map["layers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .rev()
+        .collect();
+    while let Some(layer) = layers.pop() {
+        match layer["type"].as_str() {
+            Some("tilelayer") => {
+                let data = layer["data"]
+                    .as_array()
+                    .ok_or("only CSV encoded tile layers are supported")?;
+                let gids = data
+                    .iter()
+                    .map(|gid| {
+                        gid.as_u64()
+                            .map(|gid| gid as u32)
+                            .ok_or_else(|| format!("`{}` is not a tile ID", gid))
+                    })
+                    .collect::<Result<_, _>>()?;
+                tiled.layers.push(gids);
+            }
+            Some("objectgroup") => {
+                for object in layer["objects"].as_array().into_iter().flatten() {
+                    // Like in the XML format, fall back to the class and then the name.
+                    let kind = object["type"]
+                        .as_str()
+                        .or_else(|| object["class"].as_str())
+                        .filter(|kind| !kind.is_empty())
+                        .or_else(|| object["name"].as_str());
+                    tiled.objects.push(Object {
+                        kind: kind.unwrap_or_default().to_string(),
+                        gid: object["gid"].as_u64().map(|gid| gid as u32),
+                        x: number(object, "x")?,
+                        y: number(object, "y")?,
+                        width: object["width"].as_f64().unwrap_or_default(),
+                        height: object["height"].as_f64().unwrap_or_default(),
+                    });
+                }
+            }
+            Some("group") => {
+                layers.extend(layer["layers"].as_array().into_iter().flatten().rev());
+            }
+            _ => {}
+        }
+    }
+    Ok(tiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Map {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        load(path).unwrap()
+    }
+
+    /// Tiles of the fixture levels, without their walls.
+    fn rows(map: &Map) -> Vec<Vec<Tile>> {
+        (1..map.height - 1)
+            .map(|row| map[row][1..map.width - 1].to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn tmx_map_skips_tileset_objects() {
+        assert_eq!(
+            rows(&fixture("level.tmx")),
+            vec![
+                vec![Tile::Empty, Tile::Block, Tile::Goal],
+                vec![Tile::Start, Tile::Empty, Tile::Empty],
+            ]
+        );
+    }
+
+    #[test]
+    fn tmj_map_skips_tileset_objects() {
+        assert_eq!(
+            rows(&fixture("level.tmj")),
+            vec![
+                vec![Tile::Empty, Tile::Block, Tile::Goal],
+                vec![Tile::Start, Tile::Empty, Tile::Empty],
+            ]
+        );
+    }
+}
@@ -1,4 +1,12 @@
-use crate::level::LevelManager;
+use crate::{
+    check::{self, PendingCheck},
+    editor::Editor,
+    level::{self, LevelManager},
+    player::{ElapsedTime, MoveCount},
+    respawn::RespawnCount,
+    save::Progress,
+    sim::GameState,
+};
 use bevy::prelude::*;
 
 const TEXT_COLOR: Color = Color::rgb(0.92, 0.94, 0.96);
@@ -9,7 +17,13 @@ const BUTTON_NORMAL_COLOR: Color = Color::rgb(0.37, 0.51, 0.67);
 const BUTTON_ACTIVE_COLOR: Color = Color::rgb(0.44, 0.55, 0.35);
 
 #[derive(Component, Debug)]
-pub struct PlayButton;
+pub enum MenuButton {
+    Play,
+    Editor,
+}
+
+#[derive(Component, Debug)]
+pub struct BackButton;
 
 #[derive(Component, Debug)]
 pub struct LevelButton(usize);
@@ -20,6 +34,29 @@ impl LevelButton {
     }
 }
 
+#[derive(Component, Debug)]
+pub enum CompletionButton {
+    Next,
+    Retry,
+    LevelSelect,
+    /// Return to the editor after test playing a level.
+    Editor,
+}
+
+#[derive(Component, Debug)]
+pub struct HudText;
+
+/// Title and description of the level being played.
+#[derive(Component, Debug)]
+pub struct LevelText;
+
+#[derive(Component, Debug)]
+pub struct EditorText;
+
+/// Description of the last level that failed to load.
+#[derive(Debug, Default)]
+pub struct LevelError(pub String);
+
 type InteractableButton<'a> = (&'a Interaction, &'a mut UiColor);
 
 pub fn button_system(
@@ -34,6 +71,14 @@ pub fn button_system(
     }
 }
 
+/// Describe a move count relative to its par, e.g. `Moves: 18 (par 16)`.
+fn format_moves(move_count: &MoveCount) -> String {
+    match move_count.par {
+        Some(par) => format!("Moves: {} (par {})", move_count.moves, par),
+        None => format!("Moves: {}", move_count.moves),
+    }
+}
+
 pub fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(ClearColor(crate::BACKGROUND_COLOR));
     commands.spawn_bundle(UiCameraBundle::default());
@@ -66,30 +111,66 @@ pub fn menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ),
                 ..TextBundle::default()
             });
-            main.spawn_bundle(ButtonBundle {
+            main.spawn_bundle(NodeBundle {
                 style: Style {
-                    size: Size::new(Val::Px(150.0), Val::Px(65.00)),
                     margin: Rect::all(Val::Auto),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
                     ..Style::default()
                 },
-                color: BUTTON_NORMAL_COLOR.into(),
-                ..ButtonBundle::default()
+                color: Color::NONE.into(),
+                ..NodeBundle::default()
             })
-            .insert(PlayButton)
-            .with_children(|button| {
-                button.spawn_bundle(TextBundle {
-                    text: Text::with_section(
-                        "Play",
-                        TextStyle {
-                            font,
-                            font_size: 40.0,
-                            color: TEXT_COLOR,
-                        },
-                        TextAlignment::default(),
-                    ),
-                    ..TextBundle::default()
+            .with_children(|row| {
+                row.spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(150.0), Val::Px(65.00)),
+                        margin: Rect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Style::default()
+                    },
+                    color: BUTTON_NORMAL_COLOR.into(),
+                    ..ButtonBundle::default()
+                })
+                .insert(MenuButton::Play)
+                .with_children(|button| {
+                    button.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "Play",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 40.0,
+                                color: TEXT_COLOR,
+                            },
+                            TextAlignment::default(),
+                        ),
+                        ..TextBundle::default()
+                    });
+                });
+                row.spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(150.0), Val::Px(65.00)),
+                        margin: Rect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Style::default()
+                    },
+                    color: BUTTON_NORMAL_COLOR.into(),
+                    ..ButtonBundle::default()
+                })
+                .insert(MenuButton::Editor)
+                .with_children(|button| {
+                    button.spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "Editor",
+                            TextStyle {
+                                font,
+                                font_size: 40.0,
+                                color: TEXT_COLOR,
+                            },
+                            TextAlignment::default(),
+                        ),
+                        ..TextBundle::default()
+                    });
                 });
             });
         });
@@ -99,6 +180,7 @@ pub fn level_menu_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     level_manager: Res<LevelManager>,
+    progress: Res<Progress>,
 ) {
     commands.insert_resource(ClearColor(crate::BACKGROUND_COLOR));
     commands.spawn_bundle(UiCameraBundle::default());
@@ -151,6 +233,18 @@ pub fn level_menu_setup(
                     for (i, path) in level_manager.iter().enumerate() {
                         if let Some(file_stem) = path.file_stem() {
                             if let Some(name) = file_stem.to_str() {
+                                let metadata = level::metadata(path).unwrap_or_default();
+                                let mut label = metadata.title_or(name).to_string();
+                                if let Some(difficulty) = &metadata.difficulty {
+                                    label.push_str(&format!(" ({})", difficulty));
+                                }
+                                // Show the best results of completed levels.
+                                if let Some(record) = progress.get(name) {
+                                    label.push_str(&format!(
+                                        "  best: {} moves, {:.1}s",
+                                        record.moves, record.time
+                                    ));
+                                }
                                 parent
                                     .spawn_bundle(ButtonBundle {
                                         style: Style {
@@ -170,7 +264,7 @@ pub fn level_menu_setup(
                                     .with_children(|button| {
                                         button.spawn_bundle(TextBundle {
                                             text: Text::with_section(
-                                                name,
+                                                label,
                                                 TextStyle {
                                                     font: font_medium.clone(),
                                                     font_size: 30.0,
@@ -188,3 +282,373 @@ pub fn level_menu_setup(
             });
         });
 }
+
+pub fn error_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_error: Res<LevelError>,
+) {
+    commands.insert_resource(ClearColor(crate::BACKGROUND_COLOR));
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(80.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                ..Style::default()
+            },
+            color: Color::NONE.into(),
+            ..NodeBundle::default()
+        })
+        .with_children(|main| {
+            let font_bold = asset_server.load(FONT_BOLD);
+            let font_medium = asset_server.load(FONT_MEDIUM);
+            // Title.
+            main.spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Style::default()
+                },
+                text: Text::with_section(
+                    "Level Error",
+                    TextStyle {
+                        font: font_bold.clone(),
+                        font_size: 70.0,
+                        color: TEXT_COLOR,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..TextBundle::default()
+            });
+            main.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(80.0), Val::Percent(50.0)),
+                    margin: Rect::all(Val::Auto),
+                    flex_direction: FlexDirection::ColumnReverse,
+                    justify_content: JustifyContent::SpaceEvenly,
+                    align_items: AlignItems::Center,
+                    ..Style::default()
+                },
+                color: Color::NONE.into(),
+                ..NodeBundle::default()
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        level_error.0.as_str(),
+                        TextStyle {
+                            font: font_medium,
+                            font_size: 24.0,
+                            color: TEXT_COLOR,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..TextBundle::default()
+                });
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(150.0), Val::Px(65.00)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Style::default()
+                        },
+                        color: BUTTON_NORMAL_COLOR.into(),
+                        ..ButtonBundle::default()
+                    })
+                    .insert(BackButton)
+                    .with_children(|button| {
+                        button.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "Back",
+                                TextStyle {
+                                    font: font_bold,
+                                    font_size: 40.0,
+                                    color: TEXT_COLOR,
+                                },
+                                TextAlignment::default(),
+                            ),
+                            ..TextBundle::default()
+                        });
+                    });
+            });
+        });
+}
+
+pub fn hud_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_manager: Res<LevelManager>,
+) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Rect::default()
+                },
+                ..Style::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_MEDIUM),
+                    font_size: 30.0,
+                    color: TEXT_COLOR,
+                },
+                TextAlignment::default(),
+            ),
+            ..TextBundle::default()
+        })
+        .insert(HudText);
+    // Describe the level below the stats.
+    let metadata = level_manager.metadata().unwrap_or_default();
+    let mut info = metadata
+        .title_or(level_manager.current_name().unwrap_or_default())
+        .to_string();
+    if let Some(author) = &metadata.author {
+        info.push_str(&format!(" by {}", author));
+    }
+    if let Some(description) = &metadata.description {
+        info.push_str(&format!("\n{}", description));
+    }
+    // Replaced by whether the level can be won once the search has finished.
+    info.push_str(&format!("\n{}", check::SEARCHING));
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(50.0),
+                    left: Val::Px(10.0),
+                    ..Rect::default()
+                },
+                ..Style::default()
+            },
+            text: Text::with_section(
+                info,
+                TextStyle {
+                    font: asset_server.load(FONT_MEDIUM),
+                    font_size: 20.0,
+                    color: TEXT_COLOR,
+                },
+                TextAlignment::default(),
+            ),
+            ..TextBundle::default()
+        })
+        .insert(LevelText);
+}
+
+pub fn hud_update(
+    game_state: Res<GameState>,
+    respawn_count: Res<RespawnCount>,
+    move_count: Res<MoveCount>,
+    mut text_query: Query<&mut Text, With<HudText>>,
+) {
+    if game_state.is_changed() || respawn_count.is_changed() || move_count.is_changed() {
+        let mut value = format!(
+            "{}  Deaths: {}  Restarts: {}",
+            format_moves(&move_count),
+            respawn_count.deaths,
+            respawn_count.restarts
+        );
+        let (collected, total) = game_state.gems();
+        if total > 0 {
+            value.push_str(&format!("  Gems: {}/{}", collected, total));
+        }
+        for mut text in text_query.iter_mut() {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Show whether the level can be won once the search for its shortest solution has finished.
+pub fn hud_check_update(
+    mut commands: Commands,
+    check: Option<ResMut<PendingCheck>>,
+    mut text_query: Query<&mut Text, With<LevelText>>,
+) {
+    if let Some(summary) = check.and_then(|mut check| check.poll()) {
+        commands.remove_resource::<PendingCheck>();
+        for mut text in text_query.iter_mut() {
+            let value = &mut text.sections[0].value;
+            *value = value.replace(check::SEARCHING, &summary);
+        }
+    }
+}
+
+pub fn editor_hud_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Rect::default()
+                },
+                ..Style::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(FONT_MEDIUM),
+                    font_size: 20.0,
+                    color: TEXT_COLOR,
+                },
+                TextAlignment::default(),
+            ),
+            ..TextBundle::default()
+        })
+        .insert(EditorText);
+}
+
+pub fn editor_hud_update(editor: Res<Editor>, mut text_query: Query<&mut Text, With<EditorText>>) {
+    if editor.is_changed() {
+        let (columns, rows) = editor.size();
+        let value = format!(
+            "{}  {}x{}  Brush: {:?}\n\
+             Left click: paint  Right click: erase  [ ]: brush  Arrows: resize\n\
+             Enter: test  Ctrl+S: save  Escape: menu\n{}",
+            editor.name(),
+            columns,
+            rows,
+            editor.brush(),
+            editor.status()
+        );
+        for mut text in text_query.iter_mut() {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub fn complete_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_manager: Res<LevelManager>,
+    move_count: Res<MoveCount>,
+    respawn_count: Res<RespawnCount>,
+    elapsed_time: Res<ElapsedTime>,
+) {
+    commands.insert_resource(ClearColor(crate::BACKGROUND_COLOR));
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(80.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexEnd,
+                ..Style::default()
+            },
+            color: Color::NONE.into(),
+            ..NodeBundle::default()
+        })
+        .with_children(|main| {
+            let font_bold = asset_server.load(FONT_BOLD);
+            let font_medium = asset_server.load(FONT_MEDIUM);
+            // Title.
+            main.spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Style::default()
+                },
+                text: Text::with_section(
+                    "Level Complete",
+                    TextStyle {
+                        font: font_bold.clone(),
+                        font_size: 70.0,
+                        color: TEXT_COLOR,
+                    },
+                    TextAlignment::default(),
+                ),
+                ..TextBundle::default()
+            });
+            main.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(80.0), Val::Percent(50.0)),
+                    margin: Rect::all(Val::Auto),
+                    flex_direction: FlexDirection::ColumnReverse,
+                    justify_content: JustifyContent::SpaceEvenly,
+                    align_items: AlignItems::Center,
+                    ..Style::default()
+                },
+                color: Color::NONE.into(),
+                ..NodeBundle::default()
+            })
+            .with_children(|parent| {
+                // Statistics.
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        format!(
+                            "{}  Time: {:.1}s  Deaths: {}  Restarts: {}",
+                            format_moves(&move_count),
+                            elapsed_time.0,
+                            respawn_count.deaths,
+                            respawn_count.restarts
+                        ),
+                        TextStyle {
+                            font: font_medium,
+                            font_size: 30.0,
+                            color: TEXT_COLOR,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..TextBundle::default()
+                });
+                // Buttons.
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            justify_content: JustifyContent::Center,
+                            ..Style::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..NodeBundle::default()
+                    })
+                    .with_children(|row| {
+                        let mut buttons = vec![("Retry", CompletionButton::Retry)];
+                        if level_manager.is_testing() {
+                            buttons.push(("Editor", CompletionButton::Editor));
+                        } else {
+                            buttons.push(("Levels", CompletionButton::LevelSelect));
+                        }
+                        if level_manager.has_next() {
+                            buttons.insert(0, ("Next", CompletionButton::Next));
+                        }
+                        for (label, button) in buttons {
+                            row.spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(150.0), Val::Px(65.00)),
+                                    margin: Rect::all(Val::Px(10.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Style::default()
+                                },
+                                color: BUTTON_NORMAL_COLOR.into(),
+                                ..ButtonBundle::default()
+                            })
+                            .insert(button)
+                            .with_children(|button| {
+                                button.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        label,
+                                        TextStyle {
+                                            font: font_bold.clone(),
+                                            font_size: 40.0,
+                                            color: TEXT_COLOR,
+                                        },
+                                        TextAlignment::default(),
+                                    ),
+                                    ..TextBundle::default()
+                                });
+                            });
+                        }
+                    });
+            });
+        });
+}